@@ -0,0 +1,472 @@
+use crate::vars;
+use std::io::{self, ErrorKind};
+
+/// How deep variables holding expressions may refer to each other
+const MAX_RECURSION: usize = 64;
+
+/// Evaluate an integer arithmetic expression using C operator precedence.
+/// Variables are referenced by name, without `$`, and unset or empty
+/// variables evaluate to 0. Assignment operators update shell variables.
+pub fn evaluate(expr: &str) -> io::Result<i64> {
+    evaluate_nested(expr, 0)
+}
+
+fn evaluate_nested(expr: &str, depth: usize) -> io::Result<i64> {
+    if depth > MAX_RECURSION {
+        return Err(arith_error("expression recursion level exceeded"));
+    }
+    let tokens = lex(expr)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let ast = parser.comma()?;
+    if let Some(t) = parser.tokens.get(parser.pos) {
+        return Err(arith_error(&format!("syntax error: invalid operator `{}`", t)));
+    }
+    eval(&ast, depth)
+}
+
+fn arith_error(msg: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, msg)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Num(n) => write!(f, "{}", n),
+            Token::Ident(s) => write!(f, "{}", s),
+            Token::Op(o) => write!(f, "{}", o),
+        }
+    }
+}
+
+/// operators sorted so that the longest match is tried first
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=",
+    "&&", "||", "+=", "-=", "*=", "/=", "%=", "&=", "^=", "|=",
+    "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~",
+    "?", ":", "=", "(", ")", ",",
+];
+
+fn lex(expr: &str) -> io::Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut rest = expr;
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '#' || c == '@' || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Num(parse_number(&rest[..end])?));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(arith_error(&format!("syntax error: invalid character `{}`", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parse integer literals: decimal, `0x` hexadecimal, leading `0` octal
+/// and `base#digits` where base is between 2 and 64
+fn parse_number(lit: &str) -> io::Result<i64> {
+    let invalid = || arith_error(&format!("{}: value too great for base", lit));
+
+    let (base, digits) = if let Some(i) = lit.find('#') {
+        let base: u32 = lit[..i].parse().map_err(|_| invalid())?;
+        if !(2..=64).contains(&base) {
+            return Err(arith_error(&format!("{}: invalid arithmetic base", lit)));
+        }
+        (base, &lit[i + 1..])
+    } else if lit.starts_with("0x") || lit.starts_with("0X") {
+        (16, &lit[2..])
+    } else if lit.len() > 1 && lit.starts_with('0') {
+        (8, &lit[1..])
+    } else {
+        (10, lit)
+    };
+
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut value: i64 = 0;
+    for c in digits.chars() {
+        let d = match c {
+            '0'..='9' => c as u32 - '0' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 10,
+            'A'..='Z' if base <= 36 => c as u32 - 'A' as u32 + 10,
+            'A'..='Z' => c as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err(invalid()),
+        };
+        if d >= base {
+            return Err(invalid());
+        }
+        value = value.wrapping_mul(base as i64).wrapping_add(d as i64);
+    }
+    Ok(value)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Num(i64),
+    Var(String),
+    Unary(&'static str, Box<Expr>),
+    /// `++x`/`--x` when the bool is true, otherwise `x++`/`x--`
+    Step(String, i64, bool),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    Assign(String, Option<&'static str>, Box<Expr>),
+}
+
+/// Recursive descent parser, one method per precedence level
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(o)) => Some(o),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> io::Result<()> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(arith_error(&format!("syntax error: `{}` expected", op)))
+        }
+    }
+
+    fn comma(&mut self) -> io::Result<Expr> {
+        let mut lhs = self.assignment()?;
+        while self.peek_op() == Some(",") {
+            self.pos += 1;
+            let rhs = self.assignment()?;
+            lhs = Expr::Binary(",", Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn assignment(&mut self) -> io::Result<Expr> {
+        if let (Some(Token::Ident(name)), Some(Token::Op(op))) =
+            (self.tokens.get(self.pos), self.tokens.get(self.pos + 1))
+        {
+            let op: &'static str = op;
+            if op.ends_with('=') && !["==", "!=", "<=", ">="].contains(&op) {
+                let name = name.clone();
+                let binop = match op {
+                    "=" => None,
+                    _ => Some(&op[..op.len() - 1]),
+                };
+                self.pos += 2;
+                let rhs = self.assignment()?;
+                return Ok(Expr::Assign(name, binop, Box::new(rhs)));
+            }
+        }
+        self.conditional()
+    }
+
+    fn conditional(&mut self) -> io::Result<Expr> {
+        let cond = self.binary(0)?;
+        if self.peek_op() == Some("?") {
+            self.pos += 1;
+            let a = self.comma()?;
+            self.expect(":")?;
+            let b = self.conditional()?;
+            return Ok(Expr::Cond(Box::new(cond), Box::new(a), Box::new(b)));
+        }
+        Ok(cond)
+    }
+
+    /// left associative binary operators, lowest precedence first
+    fn binary(&mut self, level: usize) -> io::Result<Expr> {
+        const LEVELS: [&[&str]; 10] = [
+            &["||"],
+            &["&&"],
+            &["|"],
+            &["^"],
+            &["&"],
+            &["==", "!="],
+            &["<", ">", "<=", ">="],
+            &["<<", ">>"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        if level == LEVELS.len() {
+            return self.power();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(op) = self.peek_op().filter(|o| LEVELS[level].contains(o)) {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// exponentiation is right associative
+    fn power(&mut self) -> io::Result<Expr> {
+        let base = self.unary()?;
+        if self.peek_op() == Some("**") {
+            self.pos += 1;
+            let exp = self.power()?;
+            return Ok(Expr::Binary("**", Box::new(base), Box::new(exp)));
+        }
+        Ok(base)
+    }
+
+    fn unary(&mut self) -> io::Result<Expr> {
+        match self.peek_op() {
+            Some(op @ "++") | Some(op @ "--") => {
+                self.pos += 1;
+                match self.tokens.get(self.pos) {
+                    Some(Token::Ident(name)) => {
+                        let name = name.clone();
+                        self.pos += 1;
+                        let delta = if op == "++" { 1 } else { -1 };
+                        Ok(Expr::Step(name, delta, true))
+                    }
+                    // `--5` is simply a double negation
+                    _ => {
+                        let inner = self.unary()?;
+                        let half = &op[..1];
+                        Ok(Expr::Unary(half, Box::new(Expr::Unary(half, Box::new(inner)))))
+                    }
+                }
+            }
+            Some(op @ "+") | Some(op @ "-") | Some(op @ "!") | Some(op @ "~") => {
+                self.pos += 1;
+                Ok(Expr::Unary(op, Box::new(self.unary()?)))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> io::Result<Expr> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Token::Num(n)) => {
+                self.pos += 1;
+                Ok(Expr::Num(n))
+            }
+            Some(Token::Ident(name)) => {
+                self.pos += 1;
+                match self.peek_op() {
+                    Some("++") => {
+                        self.pos += 1;
+                        Ok(Expr::Step(name, 1, false))
+                    }
+                    Some("--") => {
+                        self.pos += 1;
+                        Ok(Expr::Step(name, -1, false))
+                    }
+                    _ => Ok(Expr::Var(name)),
+                }
+            }
+            Some(Token::Op("(")) => {
+                self.pos += 1;
+                let inner = self.comma()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Some(t) => Err(arith_error(&format!("syntax error: operand expected (error token is `{}`)", t))),
+            None => Err(arith_error("syntax error: operand expected")),
+        }
+    }
+}
+
+fn variable(name: &str, depth: usize) -> io::Result<i64> {
+    match vars::get(name) {
+        Some(v) if !v.trim().is_empty() => evaluate_nested(&v, depth + 1),
+        _ => Ok(0),
+    }
+}
+
+fn eval(expr: &Expr, depth: usize) -> io::Result<i64> {
+    match expr {
+        Expr::Num(n) => Ok(*n),
+        Expr::Var(name) => variable(name, depth),
+        Expr::Unary(op, e) => {
+            let v = eval(e, depth)?;
+            Ok(match *op {
+                "-" => v.wrapping_neg(),
+                "!" => (v == 0) as i64,
+                "~" => !v,
+                _ => v,
+            })
+        }
+        Expr::Step(name, delta, prefix) => {
+            let old = variable(name, depth)?;
+            let new = old.wrapping_add(*delta);
+            vars::set(name, &new.to_string());
+            Ok(if *prefix { new } else { old })
+        }
+        Expr::Binary("&&", a, b) => Ok((eval(a, depth)? != 0 && eval(b, depth)? != 0) as i64),
+        Expr::Binary("||", a, b) => Ok((eval(a, depth)? != 0 || eval(b, depth)? != 0) as i64),
+        Expr::Binary(",", a, b) => {
+            eval(a, depth)?;
+            eval(b, depth)
+        }
+        Expr::Binary(op, a, b) => {
+            let a = eval(a, depth)?;
+            let b = eval(b, depth)?;
+            apply(op, a, b)
+        }
+        Expr::Cond(c, a, b) => {
+            if eval(c, depth)? != 0 {
+                eval(a, depth)
+            } else {
+                eval(b, depth)
+            }
+        }
+        Expr::Assign(name, op, rhs) => {
+            let rhs = eval(rhs, depth)?;
+            let value = match op {
+                Some(op) => apply(op, variable(name, depth)?, rhs)?,
+                None => rhs,
+            };
+            vars::set(name, &value.to_string());
+            Ok(value)
+        }
+    }
+}
+
+fn apply(op: &str, a: i64, b: i64) -> io::Result<i64> {
+    Ok(match op {
+        "+" => a.wrapping_add(b),
+        "-" => a.wrapping_sub(b),
+        "*" => a.wrapping_mul(b),
+        "/" | "%" if b == 0 => return Err(arith_error("division by 0")),
+        "/" => a.wrapping_div(b),
+        "%" => a.wrapping_rem(b),
+        "**" => {
+            if b < 0 {
+                return Err(arith_error("exponent less than 0"));
+            }
+            // by squaring, so huge exponents don't take forever
+            let (mut res, mut base, mut exp) = (1i64, a, b);
+            while exp > 0 {
+                if exp & 1 == 1 {
+                    res = res.wrapping_mul(base);
+                }
+                base = base.wrapping_mul(base);
+                exp >>= 1;
+            }
+            res
+        }
+        "<<" => a.wrapping_shl(b as u32),
+        ">>" => a.wrapping_shr(b as u32),
+        "<" => (a < b) as i64,
+        ">" => (a > b) as i64,
+        "<=" => (a <= b) as i64,
+        ">=" => (a >= b) as i64,
+        "==" => (a == b) as i64,
+        "!=" => (a != b) as i64,
+        "&" => a & b,
+        "^" => a ^ b,
+        "|" => a | b,
+        _ => return Err(arith_error(&format!("syntax error: invalid operator `{}`", op))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(7, evaluate("1 + 2 * 3").unwrap());
+        assert_eq!(9, evaluate("(1 + 2) * 3").unwrap());
+        assert_eq!(1, evaluate("1 + 2 == 3").unwrap());
+        assert_eq!(512, evaluate("2 ** 3 ** 2").unwrap());
+        assert_eq!(4, evaluate("-2 ** 2").unwrap());
+        assert_eq!(9, evaluate("1 << 3 ^ 1").unwrap());
+        assert_eq!(-7, evaluate("~7 + 1").unwrap());
+        assert_eq!(2, evaluate("7 % 5").unwrap());
+        assert_eq!(0, evaluate("!5").unwrap());
+        assert_eq!(1, evaluate("0 || 3 && 4").unwrap());
+    }
+
+    #[test]
+    fn test_ternary() {
+        assert_eq!(10, evaluate("1 ? 10 : 20").unwrap());
+        assert_eq!(30, evaluate("0 ? 10 : 0 ? 20 : 30").unwrap());
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(255, evaluate("0xff").unwrap());
+        assert_eq!(8, evaluate("010").unwrap());
+        assert_eq!(5, evaluate("2#101").unwrap());
+        assert_eq!(35, evaluate("36#z").unwrap());
+        assert_eq!(63, evaluate("64#_").unwrap());
+        assert!(evaluate("08").is_err());
+        assert!(evaluate("2#102").is_err());
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let err = evaluate("5 / (2 - 2)").unwrap_err();
+        assert_eq!("division by 0", err.to_string());
+        assert!(evaluate("5 % 0").is_err());
+    }
+
+    #[test]
+    fn test_huge_exponent() {
+        assert_eq!(1, evaluate("1 ** 20000000000").unwrap());
+        assert_eq!(0, evaluate("2 ** 20000000000").unwrap());
+        assert_eq!(1, evaluate("(-1) ** 20000000000").unwrap());
+        assert_eq!(3i64.wrapping_pow(41), evaluate("3 ** 41").unwrap());
+    }
+
+    #[test]
+    fn test_assignment_operators() {
+        assert_eq!(5, evaluate("arith_a = 5").unwrap());
+        assert_eq!(8, evaluate("arith_a += 3").unwrap());
+        assert_eq!(8, evaluate("arith_a++").unwrap());
+        assert_eq!(10, evaluate("++arith_a").unwrap());
+        assert_eq!(9, evaluate("--arith_a").unwrap());
+        assert_eq!(18, evaluate("arith_a * 2").unwrap());
+        assert_eq!(1, evaluate("arith_a <<= 0, arith_a == 9").unwrap());
+        vars::unset("arith_a");
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        assert_eq!(0, evaluate("0 && (arith_b = 1)").unwrap());
+        assert_eq!(0, evaluate("arith_b").unwrap());
+        assert_eq!(1, evaluate("1 || 1 / 0").unwrap());
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert!(evaluate("1 +").is_err());
+        assert!(evaluate("(1 + 2").is_err());
+        assert!(evaluate("1 2").is_err());
+        assert!(evaluate("3 $ 4").is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::tokenizer;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+lazy_static! {
+    /// Abbreviations by name, loaded from the config file on first use
+    static ref ABBREVIATIONS: Mutex<BTreeMap<String, String>> =
+        Mutex::new(load(&config_file()));
+}
+
+/// Implementation of the `abbr` command. Unlike an alias an abbreviation
+/// is expanded by the line editor as soon as it's typed, so the command
+/// which runs, and lands in the history, is the one on the screen.
+/// Abbreviations are saved in the config directory.
+pub struct Abbr;
+
+impl Builtin for Abbr {
+    fn name(&self) -> &'static str {
+        "abbr"
+    }
+
+    fn help(&self) -> &'static str {
+        "Manage abbreviations, which expand when followed by a space or Enter. \
+         `-e` erases them, `-l` lists their names."
+    }
+
+    fn usage(&self) -> &'static str {
+        "abbr [-a] name expansion ... | abbr -e name ... | abbr [-l]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let mut abbreviations = ABBREVIATIONS.lock().unwrap();
+        let (flag, rest) = match args.get(1).map(|a| a.as_str()) {
+            Some(f @ ("-a" | "--add" | "-e" | "--erase" | "-l" | "--list")) => (f, &args[2..]),
+            Some(f) if f.starts_with('-') => {
+                let _ = writeln!(io.stderr, "abbr: {}: invalid option", f);
+                let _ = writeln!(io.stderr, "abbr: usage: {}", self.usage());
+                return 2;
+            }
+            _ => ("", &args[1..]),
+        };
+
+        match flag {
+            "-l" | "--list" => {
+                for name in abbreviations.keys() {
+                    let _ = writeln!(io.stdout, "{}", name);
+                }
+                return 0;
+            }
+            "-e" | "--erase" => {
+                let mut status = 0;
+                for name in rest {
+                    if abbreviations.remove(name).is_none() {
+                        let _ = writeln!(io.stderr, "abbr: {}: not found", name);
+                        status = 1;
+                    }
+                }
+                return status.max(save(&abbreviations, io));
+            }
+            _ if rest.is_empty() => {
+                for (name, expansion) in abbreviations.iter() {
+                    let _ = writeln!(io.stdout, "abbr -a {} {}", name, tokenizer::quote(expansion));
+                }
+                return 0;
+            }
+            _ => {}
+        }
+
+        if rest.len() < 2 {
+            let _ = writeln!(io.stderr, "abbr: {}: expansion expected", rest[0]);
+            return 1;
+        }
+        if rest[0].contains(char::is_whitespace) || rest[0].is_empty() {
+            let _ = writeln!(io.stderr, "abbr: `{}': invalid name", rest[0]);
+            return 1;
+        }
+        abbreviations.insert(rest[0].clone(), rest[1..].join(" "));
+        save(&abbreviations, io)
+    }
+}
+
+/// Expand an abbreviation which was just typed. `line` is the text
+/// before the cursor, the abbreviation has to be its last word and the
+/// first word of a command. Returns the text with the word replaced.
+pub fn expand_before(line: &str) -> Option<String> {
+    let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+    let (before, word) = line.split_at(start);
+    if word.is_empty() || !is_command_position(before) {
+        return None;
+    }
+    let expansion = ABBREVIATIONS.lock().unwrap().get(word).cloned()?;
+    Some(format!("{}{}", before, expansion))
+}
+
+/// checks if a word following the text would be the name of a command
+fn is_command_position(before: &str) -> bool {
+    let before = before.trim_end();
+    before.is_empty() || before.ends_with([';', '|', '&', '(', '{'])
+}
+
+/// `abbreviations` in the cr4sh_ directory of the user's config directory
+fn config_file() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_default()
+        .join("cr4sh_")
+        .join("abbreviations")
+}
+
+/// Read abbreviations stored one per line, the name and the expansion
+/// separated by a tab. A missing file has no abbreviations.
+fn load(path: &Path) -> BTreeMap<String, String> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| l.split_once('\t'))
+        .map(|(name, expansion)| (name.to_string(), expansion.to_string()))
+        .collect()
+}
+
+fn store(path: &Path, abbreviations: &BTreeMap<String, String>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let contents: String = abbreviations
+        .iter()
+        .map(|(name, expansion)| format!("{}\t{}\n", name, expansion.replace('\n', " ")))
+        .collect();
+    fs::write(path, contents)
+}
+
+fn save(abbreviations: &BTreeMap<String, String>, io: &mut Io) -> ExitStatus {
+    let path = config_file();
+    match store(&path, abbreviations) {
+        Ok(()) => 0,
+        Err(e) => {
+            let _ = writeln!(io.stderr, "abbr: {}: {}", path.display(), e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+
+    #[test]
+    fn test_expand_before() {
+        ABBREVIATIONS.lock().unwrap().insert("ab_gco".to_string(), "git checkout".to_string());
+        assert_eq!(Some("git checkout".to_string()), expand_before("ab_gco"));
+        assert_eq!(Some("ls | git checkout".to_string()), expand_before("ls | ab_gco"));
+        assert_eq!(None, expand_before("echo ab_gco"));
+        assert_eq!(None, expand_before("ab_gc"));
+
+        let (_, out, _) = run_captured(&Abbr, "abbr");
+        assert!(out.contains("abbr -a ab_gco 'git checkout'\n"));
+        ABBREVIATIONS.lock().unwrap().remove("ab_gco");
+        assert_eq!(None, expand_before("ab_gco"));
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let path = std::env::temp_dir().join(format!("cr4sh_abbr_test_{}", std::process::id()));
+        let mut abbreviations = BTreeMap::new();
+        abbreviations.insert("gst".to_string(), "git status -sb".to_string());
+        store(&path, &abbreviations).unwrap();
+        assert_eq!(abbreviations, load(&path));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,184 @@
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::tokenizer::{self, raw_words};
+use crate::vars;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// Aliases defined by the user, by name
+    static ref ALIASES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+}
+
+/// Implementation of the `alias` command. `alias name=value` defines an
+/// alias, `alias name` prints it and without arguments all are printed.
+pub struct Alias;
+
+impl Builtin for Alias {
+    fn name(&self) -> &'static str {
+        "alias"
+    }
+
+    fn help(&self) -> &'static str {
+        "Define or display aliases. An alias replaces the first word of a \
+         command, when its value ends with a space the next word is checked too."
+    }
+
+    fn usage(&self) -> &'static str {
+        "alias [-p] [name[=value] ...]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let args: Vec<&String> = args[1..].iter().filter(|a| *a != "-p").collect();
+        if args.is_empty() {
+            for (name, value) in ALIASES.lock().unwrap().iter() {
+                let _ = writeln!(io.stdout, "alias {}={}", name, tokenizer::quote(value));
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, value)) if is_valid_name(name) => {
+                    ALIASES.lock().unwrap().insert(name.to_string(), value.to_string());
+                }
+                Some((name, _)) => {
+                    let _ = writeln!(io.stderr, "alias: `{}': invalid alias name", name);
+                    status = 1;
+                }
+                None => match get(arg) {
+                    Some(value) => {
+                        let _ = writeln!(io.stdout, "alias {}={}", arg, tokenizer::quote(&value));
+                    }
+                    None => {
+                        let _ = writeln!(io.stderr, "alias: {}: not found", arg);
+                        status = 1;
+                    }
+                },
+            }
+        }
+        status
+    }
+}
+
+/// Implementation of the `unalias` command, which removes
+/// the named aliases or, with `-a`, all of them.
+pub struct Unalias;
+
+impl Builtin for Unalias {
+    fn name(&self) -> &'static str {
+        "unalias"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remove aliases, `-a` removes all of them."
+    }
+
+    fn usage(&self) -> &'static str {
+        "unalias [-a] name [name ...]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        if args.len() < 2 {
+            let _ = writeln!(io.stderr, "unalias: usage: {}", self.usage());
+            return 2;
+        }
+        if args[1] == "-a" {
+            ALIASES.lock().unwrap().clear();
+            return 0;
+        }
+
+        let mut status = 0;
+        for name in args[1..].iter() {
+            if ALIASES.lock().unwrap().remove(name).is_none() {
+                let _ = writeln!(io.stderr, "unalias: {}: not found", name);
+                status = 1;
+            }
+        }
+        status
+    }
+}
+
+/// value of the alias
+pub fn get(name: &str) -> Option<String> {
+    ALIASES.lock().unwrap().get(name).cloned()
+}
+
+/// names of all aliases
+pub fn names() -> Vec<String> {
+    ALIASES.lock().unwrap().keys().cloned().collect()
+}
+
+/// Alias names can't contain characters which are special to the shell
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(|c: char| {
+            c.is_whitespace() || "/$`='\"\\|&;()<>".contains(c)
+        })
+}
+
+/// Replace the first word of every command in the line by its alias.
+/// The words of an alias are expanded again, except for the alias being
+/// expanded, so `alias ls='ls -F'` doesn't loop. When the value ends with
+/// a blank the word which follows it is checked for an alias too.
+pub fn expand(line: &str) -> String {
+    if ALIASES.lock().unwrap().is_empty() {
+        return line.to_string();
+    }
+
+    let mut res = vec![];
+    expand_words(raw_words(line), &mut res, &mut vec![], &mut true);
+    res.join(" ")
+}
+
+fn expand_words(words: Vec<String>, res: &mut Vec<String>,
+                active: &mut Vec<String>, command_position: &mut bool) {
+    for word in words {
+        let value = Some(&word)
+            .filter(|w| *command_position && !active.contains(w))
+            .filter(|w| !w.contains(['\'', '"', '\\']))
+            .and_then(|w| get(w));
+
+        if let Some(value) = value {
+            active.push(word);
+            expand_words(raw_words(&value), res, active, command_position);
+            active.pop();
+            *command_position = *command_position || value.ends_with([' ', '\t']);
+            continue;
+        }
+
+        // a command starts after an operator, assignments may precede its name
+        *command_position = matches!(word.as_str(), ";" | "&&" | "||" | "|")
+            || *command_position && vars::split_assignment(&word).is_some();
+        res.push(word);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+
+    #[test]
+    fn test_expand() {
+        run_captured(&Alias, "alias at_ll='ls -la' at_ls='ls -F' at_s='sudo ' at_loop1=at_loop2 at_loop2=at_loop1");
+        assert_eq!("ls -la /tmp ; ls -F | X=1 ls -F", expand("at_ll /tmp; at_ls | X=1 at_ls"));
+        assert_eq!("sudo ls -la at_ll", expand("at_s at_ll at_ll"));
+        assert_eq!("echo at_ll 'at_ll'", expand("echo at_ll 'at_ll'"));
+        assert_eq!("at_loop1", expand("at_loop1"));
+        run_captured(&Unalias, "unalias at_ll at_ls at_s at_loop1 at_loop2");
+        assert_eq!("at_ll", expand("at_ll"));
+    }
+
+    #[test]
+    fn test_define_and_print() {
+        let (status, _, err) = run_captured(&Alias, "alias 'at_p=echo it'\\''s' 'a/b=x' at_missing");
+        assert_eq!(1, status);
+        assert_eq!("alias: `a/b': invalid alias name\nalias: at_missing: not found\n", err);
+
+        let (_, out, _) = run_captured(&Alias, "alias at_p");
+        assert_eq!("alias at_p='echo it'\\''s'\n", out);
+        assert_eq!(0, run_captured(&Unalias, "unalias at_p").0);
+        assert_eq!(1, run_captured(&Unalias, "unalias at_p").0);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::arith;
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+
+/// Implementation of the `let` command. Every argument is evaluated
+/// as an arithmetic expression, the status is 0 when the last one
+/// evaluates to a non-zero value and 1 otherwise.
+pub struct Let;
+
+impl Builtin for Let {
+    fn name(&self) -> &'static str {
+        "let"
+    }
+
+    fn help(&self) -> &'static str {
+        "Evaluate arithmetic expressions."
+    }
+
+    fn usage(&self) -> &'static str {
+        "let arg [arg ...]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        if args.len() < 2 {
+            let _ = writeln!(io.stderr, "let: expression expected");
+            return 2;
+        }
+
+        let mut last = 0;
+        for expr in args[1..].iter() {
+            match arith::evaluate(expr) {
+                Ok(v) => last = v,
+                Err(e) => {
+                    let _ = writeln!(io.stderr, "let: {}: {}", expr, e);
+                    return 2;
+                }
+            }
+        }
+        truthiness(last)
+    }
+}
+
+/// Evaluate the body of an arithmetic command `(( expr ))`
+pub fn arithmetic_command(word: &str) -> i32 {
+    let expr = word
+        .strip_prefix("((")
+        .and_then(|w| w.strip_suffix("))"));
+
+    match expr.map(arith::evaluate) {
+        Some(Ok(v)) => truthiness(v),
+        Some(Err(e)) => {
+            eprintln!("((: {}", e);
+            2
+        }
+        None => {
+            eprintln!("Syntax error: `))` expected");
+            2
+        }
+    }
+}
+
+fn truthiness(value: i64) -> i32 {
+    if value != 0 {
+        0
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+    use crate::vars;
+
+    fn run(line: &str) -> i32 {
+        run_captured(&Let, line).0
+    }
+
+    #[test]
+    fn test_let_status() {
+        assert_eq!(0, run("let 'let_x = 3' let_x+1"));
+        assert_eq!(Some("3".to_string()), vars::get("let_x"));
+        assert_eq!(1, run("let let_x-3"));
+        assert_eq!(2, run("let 1/0"));
+        vars::unset("let_x");
+    }
+
+    #[test]
+    fn test_arithmetic_command() {
+        assert_eq!(0, arithmetic_command("(( 2 > 1 ))"));
+        assert_eq!(1, arithmetic_command("(( 0 ))"));
+        assert_eq!(2, arithmetic_command("(( 1 +"));
+    }
+}
@@ -1,31 +1,284 @@
-use crate::{shellname::ShellName, tokenizer::Tokenizer};
-use std::{
-    env::{current_dir, set_current_dir},
-    path::PathBuf,
-};
+use crate::customs::{jump, Builtin, Context, ExitStatus, Io};
+use crate::vars;
+use std::env::{current_dir, set_current_dir};
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
 
 /// Implementation of a Linux's `cd` command,
 /// which stands for change directory.
-pub fn change_directory(shell_name: &mut ShellName, line: &mut Tokenizer) {
-    assert_eq!("cd".to_string(), line.next().unwrap());
+pub struct Cd;
 
-    let path = line.next();
+impl Builtin for Cd {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn help(&self) -> &'static str {
+        "Change the shell working directory. `-` goes to the previous \
+         directory, `-P` resolves symbolic links, `-L` (the default) keeps them."
+    }
+
+    fn usage(&self) -> &'static str {
+        "cd [-L|-P] [dir]"
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let (physical, operands) = match parse_options(self, &args[1..], io) {
+            Some(opts) => opts,
+            None => return 2,
+        };
+        if operands.len() > 1 {
+            let _ = writeln!(io.stderr, "cd: too many arguments");
+            return 1;
+        }
+
+        let mut print = false;
+        let dir = match operands.first().map(|s| s.as_str()) {
+            None => match vars::get("HOME") {
+                Some(home) => home,
+                None => {
+                    let _ = writeln!(io.stderr, "cd: HOME not set");
+                    return 1;
+                }
+            },
+            Some("-") => match vars::get("OLDPWD") {
+                Some(old) => {
+                    print = true;
+                    old
+                }
+                None => {
+                    let _ = writeln!(io.stderr, "cd: OLDPWD not set");
+                    return 1;
+                }
+            },
+            Some(dir) => dir.to_string(),
+        };
 
-    let new_path: PathBuf = if path.is_some() {
-        let tmp = path.unwrap();
-        if tmp.eq("~") {
-            dirs::home_dir().unwrap()
-        } else {
-            PathBuf::from(tmp)
+        let dir = match search_cdpath(&dir) {
+            Some(found) => {
+                print = true;
+                found
+            }
+            None => PathBuf::from(dir),
+        };
+
+        match change_directory(&dir, physical) {
+            Ok(pwd) => {
+                ctx.shell_name.set_current_dir(&pwd);
+                jump::record(&pwd);
+                if print {
+                    let _ = writeln!(io.stdout, "{}", pwd);
+                }
+                0
+            }
+            Err(e) => {
+                let _ = writeln!(io.stderr, "cd: {}: {}", dir.display(), e);
+                1
+            }
         }
+    }
+}
+
+/// Implementation of the `pwd` command, which prints
+/// the logical or, with `-P`, the physical working directory.
+pub struct Pwd;
+
+impl Builtin for Pwd {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn help(&self) -> &'static str {
+        "Print the name of the current working directory."
+    }
+
+    fn usage(&self) -> &'static str {
+        "pwd [-L|-P]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let physical = match parse_options(self, &args[1..], io) {
+            Some((physical, _)) => physical,
+            None => return 2,
+        };
+        let dir = if physical { physical_dir() } else { logical_dir() };
+        match dir {
+            Ok(dir) => {
+                let _ = writeln!(io.stdout, "{}", dir);
+                0
+            }
+            Err(e) => {
+                let _ = writeln!(io.stderr, "pwd: {}", e);
+                1
+            }
+        }
+    }
+}
+
+/// Parse leading `-L` and `-P` flags, the last one wins. Returns
+/// whether paths are physical and the remaining operands.
+fn parse_options<'a>(builtin: &dyn Builtin, args: &'a [String], io: &mut Io)
+    -> Option<(bool, &'a [String])> {
+    let mut physical = false;
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--" {
+            return Some((physical, &args[i + 1..]));
+        }
+        if arg == "-" || !arg.starts_with('-') {
+            return Some((physical, &args[i..]));
+        }
+        for c in arg[1..].chars() {
+            match c {
+                'L' => physical = false,
+                'P' => physical = true,
+                _ => {
+                    let name = builtin.name();
+                    let _ = writeln!(io.stderr, "{}: -{}: invalid option", name, c);
+                    let _ = writeln!(io.stderr, "{}: usage: {}", name, builtin.usage());
+                    return None;
+                }
+            }
+        }
+    }
+    Some((physical, &[]))
+}
+
+/// A relative directory which doesn't start with `.` or `..` is looked
+/// up in the directories of `CDPATH`. Returns the directory found there.
+fn search_cdpath(dir: &str) -> Option<PathBuf> {
+    let first = Path::new(dir).components().next()?;
+    if !matches!(first, Component::Normal(_)) {
+        return None;
+    }
+    let cdpath = vars::get("CDPATH")?;
+    cdpath
+        .split(':')
+        .filter(|base| !base.is_empty())
+        .map(|base| Path::new(base).join(dir))
+        .find(|p| p.is_dir())
+}
+
+/// Change the working directory and update `PWD` and `OLDPWD`.
+/// Returns the new value of `PWD`.
+pub fn change_directory(dir: &Path, physical: bool) -> Result<String, std::io::Error> {
+    let old = logical_dir()?;
+    let pwd = if physical {
+        set_current_dir(dir)?;
+        physical_dir()?
     } else {
-        dirs::home_dir().unwrap()
+        // `..` removes the last component of the logical path
+        let target = normalize(&Path::new(&old).join(dir));
+        set_current_dir(&target)?;
+        target.to_string_lossy().to_string()
     };
 
-    if let Err(e) = set_current_dir(new_path) {
-        eprintln!("Error: {}", e);
-    } else {
-        let cur = current_dir().unwrap();
-        shell_name.set_current_dir(&cur.to_str().unwrap());
+    vars::export("OLDPWD", &old);
+    vars::export("PWD", &pwd);
+    Ok(pwd)
+}
+
+/// `PWD` if it names the current directory, the physical path otherwise
+pub fn logical_dir() -> Result<String, std::io::Error> {
+    if let Some(pwd) = vars::get("PWD") {
+        if Path::new(&pwd).is_absolute() && same_file(&pwd, ".") {
+            return Ok(pwd);
+        }
+    }
+    physical_dir()
+}
+
+fn physical_dir() -> Result<String, std::io::Error> {
+    Ok(current_dir()?.to_string_lossy().to_string())
+}
+
+/// Make sure `PWD` names the current directory when the shell starts
+pub fn init_pwd() -> Result<String, std::io::Error> {
+    let pwd = logical_dir()?;
+    vars::export("PWD", &pwd);
+    Ok(pwd)
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Remove `.` and `..` components without resolving symbolic links
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                res.pop();
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::{in_scratch_dir, run_captured};
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(PathBuf::from("/a/c"), normalize(Path::new("/a/./b/../c/")));
+        assert_eq!(PathBuf::from("/"), normalize(Path::new("/../..")));
+    }
+
+    #[test]
+    fn test_cdpath_skips_dot_paths() {
+        assert_eq!(None, search_cdpath("./src"));
+        assert_eq!(None, search_cdpath("/tmp"));
+    }
+
+    #[test]
+    fn test_cd_updates_pwd_and_oldpwd() {
+        in_scratch_dir("cd", |dir| {
+            fs::create_dir("sub").unwrap();
+            let sub = format!("{}/sub", dir);
+            assert_eq!((0, String::new(), String::new()), run_captured(&Cd, "cd sub"));
+            assert_eq!(Some(sub.clone()), vars::get("PWD"));
+            assert_eq!(Some(dir.to_string()), vars::get("OLDPWD"));
+            assert_eq!(Path::new(&sub), current_dir().unwrap());
+
+            // `cd -` prints the previous directory and swaps the two
+            assert_eq!((0, format!("{}\n", dir), String::new()), run_captured(&Cd, "cd -"));
+            assert_eq!(Some(dir.to_string()), vars::get("PWD"));
+            assert_eq!(Some(sub.clone()), vars::get("OLDPWD"));
+            assert_eq!(format!("{}\n", sub), run_captured(&Cd, "cd -").1);
+            assert_eq!(Some(sub), vars::get("PWD"));
+
+            let (status, _, err) = run_captured(&Cd, "cd missing");
+            assert_eq!(1, status);
+            assert!(err.starts_with("cd: missing: "));
+        });
+    }
+
+    #[test]
+    fn test_logical_and_physical_paths() {
+        in_scratch_dir("cd_links", |dir| {
+            fs::create_dir_all("a/real").unwrap();
+            std::os::unix::fs::symlink("a/real", "link").unwrap();
+            let (link, real) = (format!("{}/link", dir), format!("{}/a/real", dir));
+
+            assert_eq!(0, run_captured(&Cd, "cd link").0);
+            assert_eq!(Some(link.clone()), vars::get("PWD"));
+            assert_eq!(format!("{}\n", link), run_captured(&Pwd, "pwd").1);
+            assert_eq!(format!("{}\n", real), run_captured(&Pwd, "pwd -P").1);
+            // `..` leaves the link instead of its target's parent
+            assert_eq!(0, run_captured(&Cd, "cd ..").0);
+            assert_eq!(Some(dir.to_string()), vars::get("PWD"));
+
+            assert_eq!(0, run_captured(&Cd, "cd -P link").0);
+            assert_eq!(Some(real), vars::get("PWD"));
+            assert_eq!(2, run_captured(&Cd, "cd -x link").0);
+        });
     }
 }
@@ -0,0 +1,23 @@
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+
+/// Implementation of the `:` command, which does nothing but
+/// expanding its arguments and always succeeds
+pub struct Colon;
+
+impl Builtin for Colon {
+    fn name(&self) -> &'static str {
+        ":"
+    }
+
+    fn help(&self) -> &'static str {
+        "Do nothing, the command always succeeds."
+    }
+
+    fn usage(&self) -> &'static str {
+        ": [arguments]"
+    }
+
+    fn run(&self, _: &mut Context, _: &[String], _: &mut Io) -> ExitStatus {
+        0
+    }
+}
@@ -0,0 +1,94 @@
+use crate::customs::help::{find, Found};
+use crate::customs::{self, Builtin, Context, ExitStatus, Io};
+use crate::tokenizer;
+
+/// Implementation of the `command` command. It runs a builtin or a program
+/// skipping aliases, with `-v` it prints what would be run and with `-V`
+/// describes it like `type`.
+pub struct Command;
+
+impl Builtin for Command {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run a command ignoring aliases, or tell what would be run: `-v` \
+         prints the alias, builtin name or path of a program, `-V` a description."
+    }
+
+    fn usage(&self) -> &'static str {
+        "command [-vV] name [arg ...]"
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let (verbose, rest) = match args.get(1).map(|a| a.as_str()) {
+            Some("-v") => (Some(false), &args[2..]),
+            Some("-V") => (Some(true), &args[2..]),
+            Some("--") => (None, &args[2..]),
+            Some(a) if a.starts_with('-') => {
+                let _ = writeln!(io.stderr, "command: {}: invalid option", a);
+                let _ = writeln!(io.stderr, "command: usage: {}", self.usage());
+                return 2;
+            }
+            _ => (None, &args[1..]),
+        };
+
+        match verbose {
+            Some(verbose) => describe(rest, verbose, io),
+            None if rest.is_empty() => 0,
+            None => match customs::lookup(&rest[0]) {
+                Some(builtin) => builtin.run(ctx, rest, io),
+                None => {
+                    let _ = io.stdout.flush();
+                    crate::run_program(ctx.shell_name, rest, &[]).unwrap_or_else(|e| {
+                        let _ = writeln!(io.stderr, "command: {}", e);
+                        1
+                    })
+                }
+            },
+        }
+    }
+}
+
+fn describe(names: &[String], verbose: bool, io: &mut Io) -> ExitStatus {
+    let mut status = 0;
+    for name in names {
+        let _ = match find(name, false).first() {
+            Some(found) if verbose => writeln!(io.stdout, "{}", found.describe(name)),
+            Some(Found::Alias(value)) => {
+                writeln!(io.stdout, "alias {}={}", name, tokenizer::quote(value))
+            }
+            Some(Found::Builtin) => writeln!(io.stdout, "{}", name),
+            Some(Found::File(path, _)) => writeln!(io.stdout, "{}", path.display()),
+            None => {
+                status = 1;
+                if verbose {
+                    writeln!(io.stderr, "command: {}: not found", name)
+                } else {
+                    Ok(())
+                }
+            }
+        };
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+
+    #[test]
+    fn test_describe() {
+        let (status, out, _) = run_captured(&Command, "command -v cd sh no_such_command_x");
+        assert_eq!(1, status);
+        assert!(out.starts_with("cd\n/"));
+        assert!(out.ends_with("/sh\n"));
+
+        let (status, out, err) = run_captured(&Command, "command -V pwd no_such_command_x");
+        assert_eq!(1, status);
+        assert_eq!("pwd is a shell builtin\n", out);
+        assert_eq!("command: no_such_command_x: not found\n", err);
+    }
+}
@@ -0,0 +1,352 @@
+use crate::customs::cd::{change_directory, logical_dir};
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::vars;
+use std::path::Path;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// Directories saved by `pushd`, the most recent first.
+    /// The current directory is always the top of the stack
+    /// and isn't stored here.
+    static ref STACK: Mutex<Vec<String>> = Mutex::new(vec![]);
+}
+
+/// Implementation of the `pushd` command. `pushd dir` saves the current
+/// directory on the stack and changes to `dir`, `pushd +N`/`-N` rotates the
+/// stack so the N-th entry is on top and without arguments the two top
+/// directories are exchanged.
+pub struct Pushd;
+
+impl Builtin for Pushd {
+    fn name(&self) -> &'static str {
+        "pushd"
+    }
+
+    fn help(&self) -> &'static str {
+        "Add a directory to the top of the directory stack, or rotate the stack."
+    }
+
+    fn usage(&self) -> &'static str {
+        "pushd [dir | +N | -N]"
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let mut dirs = match full_stack() {
+            Ok(d) => d,
+            Err(e) => {
+                let _ = writeln!(io.stderr, "pushd: {}", e);
+                return 1;
+            }
+        };
+
+        let target = match args.get(1) {
+            None if dirs.len() < 2 => {
+                let _ = writeln!(io.stderr, "pushd: no other directory");
+                return 1;
+            }
+            None => {
+                dirs.swap(0, 1);
+                dirs[0].clone()
+            }
+            Some(arg) => match parse_index(arg, dirs.len()) {
+                Some(Ok(n)) => {
+                    dirs.rotate_left(n);
+                    dirs[0].clone()
+                }
+                Some(Err(())) => {
+                    let _ = writeln!(io.stderr, "pushd: {}: directory stack index out of range", arg);
+                    return 1;
+                }
+                None => {
+                    dirs.insert(0, arg.to_string());
+                    arg.to_string()
+                }
+            },
+        };
+
+        go_to(ctx, &target, dirs, io)
+    }
+}
+
+/// Implementation of the `popd` command, which removes the top directory
+/// from the stack and changes to the new top, or with `+N`/`-N` removes
+/// the N-th entry of the stack.
+pub struct Popd;
+
+impl Builtin for Popd {
+    fn name(&self) -> &'static str {
+        "popd"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remove directories from the directory stack."
+    }
+
+    fn usage(&self) -> &'static str {
+        "popd [+N | -N]"
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let mut dirs = match full_stack() {
+            Ok(d) => d,
+            Err(e) => {
+                let _ = writeln!(io.stderr, "popd: {}", e);
+                return 1;
+            }
+        };
+        if dirs.len() < 2 {
+            let _ = writeln!(io.stderr, "popd: directory stack empty");
+            return 1;
+        }
+
+        let n = match args.get(1).map(|a| (a, parse_index(a, dirs.len()))) {
+            None => 0,
+            Some((_, Some(Ok(n)))) => n,
+            Some((arg, Some(Err(())))) => {
+                let _ = writeln!(io.stderr, "popd: {}: directory stack index out of range", arg);
+                return 1;
+            }
+            Some((arg, None)) => {
+                let _ = writeln!(io.stderr, "popd: {}: invalid argument", arg);
+                let _ = writeln!(io.stderr, "popd: usage: {}", self.usage());
+                return 2;
+            }
+        };
+
+        dirs.remove(n);
+        if n > 0 {
+            set_stack(dirs);
+            print_stack(io);
+            return 0;
+        }
+        let target = dirs[0].clone();
+        go_to(ctx, &target, dirs, io)
+    }
+}
+
+/// Implementation of the `dirs` command, which lists the directory stack
+pub struct Dirs;
+
+impl Builtin for Dirs {
+    fn name(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn help(&self) -> &'static str {
+        "Display the directory stack, `-v` numbers the entries, \
+         `-p` prints one per line, `-l` doesn't shorten the home \
+         directory to `~` and `-c` clears the stack."
+    }
+
+    fn usage(&self) -> &'static str {
+        "dirs [-clpv] [+N | -N]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let (mut clear, mut long, mut one_per_line, mut numbered) = (false, false, false, false);
+        let mut index = None;
+        for arg in args[1..].iter() {
+            if parse_index(arg, usize::MAX).is_some() {
+                index = Some(arg);
+                continue;
+            }
+            let flags = arg.strip_prefix('-').unwrap_or("?");
+            for c in flags.chars() {
+                match c {
+                    'c' => clear = true,
+                    'l' => long = true,
+                    'p' => one_per_line = true,
+                    'v' => numbered = true,
+                    _ => {
+                        let _ = writeln!(io.stderr, "dirs: {}: invalid option", arg);
+                        let _ = writeln!(io.stderr, "dirs: usage: {}", self.usage());
+                        return 2;
+                    }
+                }
+            }
+        }
+
+        if clear {
+            STACK.lock().unwrap().clear();
+            return 0;
+        }
+        let dirs = match full_stack() {
+            Ok(d) => d,
+            Err(e) => {
+                let _ = writeln!(io.stderr, "dirs: {}", e);
+                return 1;
+            }
+        };
+        let dirs = match index.map(|i| (i, parse_index(i, dirs.len()))) {
+            None => dirs,
+            Some((_, Some(Ok(n)))) => vec![dirs[n].clone()],
+            Some((arg, _)) => {
+                let _ = writeln!(io.stderr, "dirs: {}: directory stack index out of range", arg);
+                return 1;
+            }
+        };
+        write_stack(io, &dirs, numbered, one_per_line, long);
+        0
+    }
+}
+
+/// Entry of the stack for `~N`, `~+N` and `~-N`. `N` counts from the
+/// top of the stack, which is the current directory, `-N` from the bottom.
+pub fn entry(index: &str) -> Option<String> {
+    let dirs = full_stack().ok()?;
+    let index = if index.starts_with(['+', '-']) {
+        index.to_string()
+    } else {
+        format!("+{}", index)
+    };
+    match parse_index(&index, dirs.len())? {
+        Ok(n) => Some(dirs[n].clone()),
+        Err(()) => None,
+    }
+}
+
+/// The current directory followed by the saved ones
+fn full_stack() -> Result<Vec<String>, std::io::Error> {
+    let mut dirs = vec![logical_dir()?];
+    dirs.extend(STACK.lock().unwrap().iter().cloned());
+    Ok(dirs)
+}
+
+/// store all but the current directory of the stack
+fn set_stack(mut dirs: Vec<String>) {
+    dirs.remove(0);
+    *STACK.lock().unwrap() = dirs;
+}
+
+/// Change to the directory on top of the updated stack and print it.
+/// The stack is left as it was when the directory can't be entered.
+fn go_to(ctx: &mut Context, target: &str, mut dirs: Vec<String>, io: &mut Io) -> ExitStatus {
+    match change_directory(Path::new(target), false) {
+        Ok(pwd) => {
+            ctx.shell_name.set_current_dir(&pwd);
+            dirs[0] = pwd;
+            set_stack(dirs);
+            print_stack(io);
+            0
+        }
+        Err(e) => {
+            let _ = writeln!(io.stderr, "{}: {}", target, e);
+            1
+        }
+    }
+}
+
+/// `+N` counts from the top of a stack of `len` entries, `-N` from the
+/// bottom. Returns `None` when `arg` isn't an index and an error when
+/// it's out of range.
+fn parse_index(arg: &str, len: usize) -> Option<Result<usize, ()>> {
+    let from_top = arg.starts_with('+');
+    if !from_top && !arg.starts_with('-') {
+        return None;
+    }
+    let n: usize = arg[1..].parse().ok()?;
+    if n >= len {
+        return Some(Err(()));
+    }
+    Some(Ok(if from_top { n } else { len - 1 - n }))
+}
+
+/// print the stack after it was changed
+fn print_stack(io: &mut Io) {
+    if let Ok(dirs) = full_stack() {
+        write_stack(io, &dirs, false, false, false);
+    }
+}
+
+fn write_stack(io: &mut Io, dirs: &[String], numbered: bool, one_per_line: bool, long: bool) {
+    let dirs = dirs.iter().map(|d| if long { d.clone() } else { shorten(d) });
+    if numbered {
+        for (i, dir) in dirs.enumerate() {
+            let _ = writeln!(io.stdout, "{:>2}  {}", i, dir);
+        }
+    } else if one_per_line {
+        for dir in dirs {
+            let _ = writeln!(io.stdout, "{}", dir);
+        }
+    } else {
+        let _ = writeln!(io.stdout, "{}", dirs.collect::<Vec<_>>().join(" "));
+    }
+}
+
+/// replace the home directory at the start of the path with `~`
+fn shorten(dir: &str) -> String {
+    match vars::get("HOME") {
+        Some(home) if !home.is_empty() && home != "/" => match dir.strip_prefix(&home) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+            _ => dir.to_string(),
+        },
+        _ => dir.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::{in_scratch_dir, run_captured};
+    use std::fs;
+
+    #[test]
+    fn test_parse_index() {
+        assert_eq!(Some(Ok(1)), parse_index("+1", 3));
+        assert_eq!(Some(Ok(2)), parse_index("-0", 3));
+        assert_eq!(Some(Err(())), parse_index("+3", 3));
+        assert_eq!(None, parse_index("dir", 3));
+        assert_eq!(None, parse_index("-x", 3));
+    }
+
+    #[test]
+    fn test_shorten() {
+        let home = vars::get("HOME").unwrap();
+        assert_eq!("~/src", shorten(&format!("{}/src", home)));
+        assert_eq!(format!("{}x", home), shorten(&format!("{}x", home)));
+    }
+
+    #[test]
+    fn test_push_rotate_and_pop() {
+        in_scratch_dir("dirstack", |d| {
+            for sub in ["a", "b", "c"].iter() {
+                fs::create_dir(sub).unwrap();
+            }
+            let line = |dirs: &[&str]| {
+                let full: Vec<_> = dirs.iter().map(|s| format!("{}{}", d, s)).collect();
+                format!("{}\n", full.join(" "))
+            };
+            let cwd = || vars::get("PWD").unwrap();
+            assert_eq!(0, run_captured(&Dirs, "dirs -c").0);
+            assert_eq!(line(&["/a", ""]), run_captured(&Pushd, "pushd a").1);
+            run_captured(&Pushd, "pushd ../b");
+            run_captured(&Pushd, "pushd ../c");
+            let numbered = format!(" 0  {0}/c\n 1  {0}/b\n 2  {0}/a\n 3  {0}\n", d);
+            assert_eq!(numbered, run_captured(&Dirs, "dirs -v").1);
+
+            // `~N` counts from the top, `~-N` from the bottom
+            assert_eq!(Some(format!("{}/b", d)), entry("1"));
+            assert_eq!(Some(format!("{}/c", d)), entry("+0"));
+            assert_eq!(Some(d.to_string()), entry("-0"));
+            assert_eq!(None, entry("4"));
+
+            assert_eq!(line(&["/a", "", "/c", "/b"]), run_captured(&Pushd, "pushd +2").1);
+            assert_eq!(format!("{}/a", d), cwd());
+            assert_eq!(line(&["/c", "/b", "/a", ""]), run_captured(&Pushd, "pushd -1").1);
+            assert_eq!(format!("{}/c", d), cwd());
+            assert_eq!(1, run_captured(&Pushd, "pushd +4").0);
+
+            // removing other entries stays in the directory
+            assert_eq!(line(&["/c", "/a", ""]), run_captured(&Popd, "popd +1").1);
+            assert_eq!(line(&["/c", "/a"]), run_captured(&Popd, "popd -0").1);
+            assert_eq!(format!("{}/c", d), cwd());
+            assert_eq!(line(&["/a"]), run_captured(&Popd, "popd").1);
+            assert_eq!(format!("{}/a", d), cwd());
+            assert_eq!(1, run_captured(&Popd, "popd").0);
+
+            run_captured(&Pushd, "pushd ../b");
+            assert_eq!(0, run_captured(&Dirs, "dirs -c").0);
+            assert_eq!(line(&["/b"]), run_captured(&Dirs, "dirs").1);
+        });
+    }
+}
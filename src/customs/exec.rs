@@ -0,0 +1,172 @@
+use crate::customs::{hash, Builtin, Context, ExitStatus, Io};
+use crate::signals;
+use crate::sys::io_err;
+use nix::unistd::{execv, execve};
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Implementation of the `exec` command. It replaces the shell with the
+/// program, or without one keeps its redirections for the rest of the
+/// session, e.g. `exec 2>/dev/null` or `exec 3>log`.
+pub struct Exec;
+
+/// How the program is started
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    /// `-a name` is passed as the zeroth argument
+    name: Option<String>,
+    /// `-c` runs it with an empty environment
+    clear: bool,
+    /// `-l` puts a dash before the zeroth argument, like `login` does
+    login: bool,
+}
+
+impl Builtin for Exec {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    fn help(&self) -> &'static str {
+        "Replace the shell with the command. Without a command its \
+         redirections stay in effect in the shell."
+    }
+
+    fn usage(&self) -> &'static str {
+        "exec [-cl] [-a name] [command [arg ...]]"
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let (opts, command) = match parse_options(&args[1..]) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let _ = writeln!(io.stderr, "exec: {}", e);
+                let _ = writeln!(io.stderr, "exec: usage: {}", self.usage());
+                return 2;
+            }
+        };
+        if command.is_empty() {
+            ctx.keep_redirections = true;
+            return 0;
+        }
+
+        let program = match hash::resolve(&command[0]) {
+            Ok(p) => p,
+            Err(e) => return not_runnable(&command[0], &e, io),
+        };
+        let _ = io.stdout.flush();
+        let _ = io.stderr.flush();
+        let e = replace_shell(&program, &opts, command);
+        not_runnable(&command[0], &e, io)
+    }
+}
+
+/// Split the options of `exec` from the command
+fn parse_options(args: &[String]) -> Result<(Options, &[String]), String> {
+    let mut opts = Options::default();
+    let mut rest = args;
+    while let Some(flags) = rest.first().and_then(|a| a.strip_prefix('-')) {
+        if flags.is_empty() {
+            break;
+        }
+        rest = &rest[1..];
+        if flags == "-" {
+            break;
+        }
+        for (i, c) in flags.char_indices() {
+            match c {
+                'c' => opts.clear = true,
+                'l' => opts.login = true,
+                'a' => {
+                    // the name is the rest of the word or the next argument
+                    let name = match &flags[i + 1..] {
+                        "" => {
+                            let name = rest.first().ok_or("-a: option requires an argument")?;
+                            rest = &rest[1..];
+                            name.clone()
+                        }
+                        name => name.to_string(),
+                    };
+                    opts.name = Some(name);
+                    break;
+                }
+                _ => return Err(format!("-{}: invalid option", c)),
+            }
+        }
+    }
+    Ok((opts, rest))
+}
+
+/// Execute the program in place of the shell. It only returns if that failed.
+fn replace_shell(program: &Path, opts: &Options, command: &[String]) -> io::Error {
+    let mut argv0 = opts.name.clone().unwrap_or_else(|| command[0].clone());
+    if opts.login {
+        argv0.insert(0, '-');
+    }
+    let cstr = |s: &[u8]| CString::new(s).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput));
+    let args: io::Result<Vec<CString>> = Some(argv0.as_str())
+        .into_iter()
+        .chain(command[1..].iter().map(|a| a.as_str()))
+        .map(|a| cstr(a.as_bytes()))
+        .collect();
+    let (path, args) = match (cstr(program.as_os_str().as_bytes()), args) {
+        (Ok(path), Ok(args)) => (path, args),
+        (Err(e), _) | (_, Err(e)) => return e,
+    };
+
+    if let Err(e) = signals::restore_defaults() {
+        return e;
+    }
+    let result = if opts.clear {
+        execve::<CString, CString>(&path, &args, &[])
+    } else {
+        execv(&path, &args)
+    };
+    let e = result.map(|_| ()).map_err(io_err).unwrap_err();
+    // the shell goes on, so it has to ignore the signals again
+    if signals::is_interactive() {
+        let _ = signals::ignore_interactive();
+    }
+    e
+}
+
+/// status of a command which can't be executed, like for other programs
+fn not_runnable(name: &str, e: &io::Error, io: &mut Io) -> ExitStatus {
+    let _ = writeln!(io.stderr, "exec: {}: {}", name, e);
+    if e.kind() == io::ErrorKind::NotFound {
+        127
+    } else {
+        126
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::{args, run_captured};
+
+    #[test]
+    fn test_parse_options() {
+        let words = args("-cl -a name sh -c x");
+        let (opts, command) = parse_options(&words).unwrap();
+        assert_eq!(Options { name: Some("name".to_string()), clear: true, login: true }, opts);
+        assert_eq!(args("sh -c x"), command);
+
+        let words = args("-aname -- -x");
+        let (opts, command) = parse_options(&words).unwrap();
+        assert_eq!(Some("name".to_string()), opts.name);
+        assert_eq!(args("-x"), command);
+
+        assert!(parse_options(&args("-a")).is_err());
+        assert!(parse_options(&args("-x ls")).is_err());
+    }
+
+    #[test]
+    fn test_not_found() {
+        let (status, _, err) = run_captured(&Exec, "exec no_such_command_x");
+        assert_eq!(127, status);
+        assert_eq!("exec: no_such_command_x: command not found\n", err);
+        assert_eq!(126, run_captured(&Exec, "exec /tmp/").0);
+    }
+}
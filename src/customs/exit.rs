@@ -0,0 +1,102 @@
+use crate::customs::{history, Builtin, Context, ExitStatus, Io};
+use crate::shellname::ShellName;
+use crate::{signals, sys, vars};
+use std::env;
+use std::io::{self, Write};
+use std::process;
+
+/// Implementation of the `exit` command and of `logout`, which only
+/// leaves a login shell. Registered once for each name.
+pub struct Exit(pub &'static str);
+
+impl Builtin for Exit {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn help(&self) -> &'static str {
+        if self.0 == "logout" {
+            "Exit a login shell with the status N, or the status of the last command."
+        } else {
+            "Exit the shell with the status N, or the status of the last command."
+        }
+    }
+
+    fn usage(&self) -> &'static str {
+        if self.0 == "logout" {
+            "logout [n]"
+        } else {
+            "exit [n]"
+        }
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        if self.0 == "logout" && !is_login_shell() {
+            let _ = writeln!(io.stderr, "logout: not login shell: use `exit'");
+            return 1;
+        }
+        let status = match exit_status(self.0, &args[1..], io) {
+            Ok(s) => s,
+            Err(s) => return s,
+        };
+        let _ = io.stdout.flush();
+        leave(ctx.shell_name, status)
+    }
+}
+
+/// Status the shell exits with. A status which isn't a number still exits
+/// the shell, with status 2, too many arguments only fail the command.
+fn exit_status(name: &str, args: &[String], io: &mut Io) -> Result<i32, ExitStatus> {
+    match args {
+        [] => Ok(vars::status()),
+        [n] => match n.parse::<i64>() {
+            Ok(n) => Ok((n & 0xff) as i32),
+            Err(_) => {
+                let _ = writeln!(io.stderr, "{}: {}: numeric argument required", name, n);
+                Ok(2)
+            }
+        },
+        _ => {
+            let _ = writeln!(io.stderr, "{}: too many arguments", name);
+            Err(1)
+        }
+    }
+}
+
+/// a login shell is started with a name beginning with `-`
+fn is_login_shell() -> bool {
+    env::args().next().map(|a| a.starts_with('-')).unwrap_or(false)
+}
+
+/// Leave the shell with the status after running the `EXIT` trap.
+/// The interactive shell saves its history first. Without job control
+/// there are no stopped jobs to warn about or to send SIGHUP to.
+pub fn leave(shell_name: &mut ShellName, status: i32) -> ! {
+    signals::run_trap(shell_name, signals::EXIT);
+    if signals::is_interactive() && !sys::in_subshell() {
+        eprintln!("exit");
+        if let Err(e) = history::save() {
+            eprintln!("Error: {}", e);
+        }
+    }
+    let _ = io::stdout().flush();
+    process::exit(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::{args, captured};
+
+    #[test]
+    fn test_exit_status() {
+        let (status, _, _) = captured(|io| exit_status("exit", &args("258"), io));
+        assert_eq!(Ok(2), status);
+        let (status, _, err) = captured(|io| exit_status("exit", &args("x"), io));
+        assert_eq!(Ok(2), status);
+        assert_eq!("exit: x: numeric argument required\n", err);
+        let (status, _, err) = captured(|io| exit_status("exit", &args("1 2"), io));
+        assert_eq!(Err(1), status);
+        assert_eq!("exit: too many arguments\n", err);
+    }
+}
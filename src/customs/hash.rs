@@ -0,0 +1,249 @@
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::vars;
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Programs found in `PATH`, remembered so the directories aren't
+/// searched every time. The table is emptied when `PATH` changes.
+#[derive(Default)]
+struct Table {
+    path: String,
+    programs: BTreeMap<String, (PathBuf, usize)>,
+}
+
+lazy_static! {
+    static ref TABLE: Mutex<Table> = Mutex::new(Table::default());
+}
+
+/// Implementation of the `hash` command. Without arguments it lists the
+/// remembered programs with the number of times they were run, names are
+/// looked up and remembered, `-r` forgets all of them and `-d` the named
+/// ones. `-t` prints where the programs are.
+pub struct Hash;
+
+impl Builtin for Hash {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn help(&self) -> &'static str {
+        "Remember or display the full paths of programs. `-r` forgets \
+         all of them, `-d` the named ones and `-t` prints their paths."
+    }
+
+    fn usage(&self) -> &'static str {
+        "hash [-r] [-d | -t] [name ...]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let (mut forget, mut delete, mut print) = (false, false, false);
+        let mut names = &args[1..];
+        while let Some(flags) = names.first().and_then(|a| a.strip_prefix('-')) {
+            names = &names[1..];
+            if flags == "-" {
+                break;
+            }
+            for c in flags.chars() {
+                match c {
+                    'r' => forget = true,
+                    'd' => delete = true,
+                    't' => print = true,
+                    _ => {
+                        let _ = writeln!(io.stderr, "hash: -{}: invalid option", c);
+                        let _ = writeln!(io.stderr, "hash: usage: {}", self.usage());
+                        return 2;
+                    }
+                }
+            }
+        }
+
+        let mut table = table();
+        if forget {
+            table.programs.clear();
+        }
+        if names.is_empty() {
+            if !forget {
+                list(&table, io);
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for name in names {
+            let found = if delete {
+                table.programs.remove(name).is_some()
+            } else if print {
+                match table.programs.get(name) {
+                    Some((path, _)) if names.len() > 1 => {
+                        writeln!(io.stdout, "{}\t{}", name, path.display()).is_ok()
+                    }
+                    Some((path, _)) => writeln!(io.stdout, "{}", path.display()).is_ok(),
+                    None => false,
+                }
+            } else if name.contains('/') {
+                true
+            } else {
+                match search(name) {
+                    Ok(path) => {
+                        table.programs.insert(name.clone(), (path, 0));
+                        true
+                    }
+                    Err(_) => false,
+                }
+            };
+            if !found {
+                let _ = writeln!(io.stderr, "hash: {}: not found", name);
+                status = 1;
+            }
+        }
+        status
+    }
+}
+
+fn list(table: &Table, io: &mut Io) {
+    if table.programs.is_empty() {
+        let _ = writeln!(io.stderr, "hash: hash table empty");
+        return;
+    }
+    let _ = writeln!(io.stdout, "hits\tcommand");
+    for (path, hits) in table.programs.values() {
+        let _ = writeln!(io.stdout, "{:>4}\t{}", hits, path.display());
+    }
+}
+
+/// the table, emptied first when `PATH` changed since it was filled
+fn table() -> std::sync::MutexGuard<'static, Table> {
+    let mut table = TABLE.lock().unwrap();
+    let path = vars::get("PATH").unwrap_or_default();
+    if table.path != path {
+        table.path = path;
+        table.programs.clear();
+    }
+    table
+}
+
+/// Find the program to run for a command name. A name with a slash is
+/// the path of the program, others are searched in `PATH` unless they
+/// were found before. The error is `NotFound` when there is no such
+/// program, other errors mean it can't be executed.
+pub fn resolve(name: &str) -> io::Result<PathBuf> {
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        check_executable(&path)?;
+        return Ok(path);
+    }
+
+    let mut table = table();
+    if let Some((path, hits)) = table.programs.get_mut(name) {
+        if is_executable(path) {
+            *hits += 1;
+            return Ok(path.clone());
+        }
+    }
+    let path = search(name)?;
+    table.programs.insert(name.to_string(), (path.clone(), 1));
+    Ok(path)
+}
+
+/// path of the program when it's in the table
+pub fn hashed(name: &str) -> Option<PathBuf> {
+    table().programs.get(name).map(|(path, _)| path.clone())
+}
+
+/// Every program with the name in the directories of `PATH`
+pub fn find_all(name: &str) -> Vec<PathBuf> {
+    if name.contains('/') {
+        return Some(PathBuf::from(name)).filter(|p| is_executable(p)).into_iter().collect();
+    }
+    path_dirs().map(|dir| dir.join(name)).filter(|p| is_executable(p)).collect()
+}
+
+/// Names of all programs in the directories of `PATH`
+pub fn programs() -> Vec<String> {
+    path_dirs()
+        .filter_map(|dir| dir.read_dir().ok())
+        .flat_map(|entries| entries.filter_map(|e| e.ok()))
+        .filter(|e| is_executable(&e.path()))
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+/// First executable with the name in `PATH`. When there is only a file
+/// which isn't executable the error tells so.
+fn search(name: &str) -> io::Result<PathBuf> {
+    let mut denied = None;
+    for path in path_dirs().map(|dir| dir.join(name)) {
+        match check_executable(&path) {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() != io::ErrorKind::NotFound && denied.is_none() => denied = Some(e),
+            Err(_) => {}
+        }
+    }
+    Err(denied.unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "command not found")))
+}
+
+/// directories of `PATH`, an empty entry is the current directory
+fn path_dirs() -> impl Iterator<Item = PathBuf> {
+    let path = vars::get("PATH").unwrap_or_default();
+    env::split_paths(&path)
+        .map(|dir| if dir.as_os_str().is_empty() { PathBuf::from(".") } else { dir })
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+fn check_executable(path: &Path) -> io::Result<()> {
+    let meta = path.metadata().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(io::ErrorKind::NotFound, "No such file or directory"),
+        _ => e,
+    })?;
+    if meta.is_dir() {
+        return Err(io::Error::other("Is a directory"));
+    }
+    if meta.permissions().mode() & 0o111 == 0 {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied"));
+    }
+    Ok(())
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    check_executable(path).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+    use std::fs;
+
+    #[test]
+    fn test_resolve_errors() {
+        assert_eq!(io::ErrorKind::NotFound, resolve("no_such_command_x").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::NotFound, resolve("./no/such/file").unwrap_err().kind());
+        assert_eq!("Is a directory", resolve("/tmp/").unwrap_err().to_string());
+
+        let name = "hash_test_not_executable";
+        fs::write(name, "echo hi\n").unwrap();
+        let err = resolve(&format!("./{}", name)).unwrap_err();
+        assert_eq!(io::ErrorKind::PermissionDenied, err.kind());
+        fs::remove_file(name).unwrap();
+    }
+
+    #[test]
+    fn test_hash_table() {
+        let sh = resolve("sh").unwrap();
+        assert_eq!(Some(sh.clone()), hashed("sh"));
+        let (status, out, _) = run_captured(&Hash, "hash -t sh");
+        assert_eq!(0, status);
+        assert_eq!(format!("{}\n", sh.display()), out);
+
+        assert_eq!(0, run_captured(&Hash, "hash -d sh").0);
+        assert_eq!(None, hashed("sh"));
+        let (status, _, err) = run_captured(&Hash, "hash no_such_command_x");
+        assert_eq!(1, status);
+        assert_eq!("hash: no_such_command_x: not found\n", err);
+    }
+}
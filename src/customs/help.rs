@@ -0,0 +1,204 @@
+use crate::customs::{self, alias, hash, Builtin, Context, ExitStatus, Io};
+use std::path::PathBuf;
+
+/// Implementation of the `help` command. Without arguments it lists
+/// usage of every builtin, otherwise describes the named ones.
+pub struct Help;
+
+impl Builtin for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn help(&self) -> &'static str {
+        "Display information about builtin commands."
+    }
+
+    fn usage(&self) -> &'static str {
+        "help [name ...]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        if args.len() < 2 {
+            let _ = writeln!(io.stdout, "cr4sh_ builtin commands:\n");
+            for builtin in customs::builtins() {
+                let _ = writeln!(io.stdout, "  {}", builtin.usage());
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for name in args[1..].iter() {
+            match customs::lookup(name) {
+                Some(builtin) => {
+                    let _ = writeln!(io.stdout, "{}: {}\n    {}",
+                                     name, builtin.usage(), builtin.help());
+                }
+                None => {
+                    let _ = writeln!(io.stderr, "help: no help topics match `{}'", name);
+                    status = 1;
+                }
+            }
+        }
+        status
+    }
+}
+
+/// Implementation of the `type` command, which tells how each name
+/// would be interpreted as a command. `-t` prints only the kind of the
+/// command, `-p` only the path of a program and `-a` all the meanings.
+pub struct Type;
+
+impl Builtin for Type {
+    fn name(&self) -> &'static str {
+        "type"
+    }
+
+    fn help(&self) -> &'static str {
+        "Display how each name would be interpreted if used as a command. \
+         `-t` prints alias, builtin or file, `-p` the path of a program, \
+         `-P` searches PATH even for builtins and `-a` shows every meaning."
+    }
+
+    fn usage(&self) -> &'static str {
+        "type [-aptP] name [name ...]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let (mut all, mut kind, mut path, mut force_path) = (false, false, false, false);
+        let mut names = &args[1..];
+        while let Some(flags) = names.first().and_then(|a| a.strip_prefix('-')) {
+            names = &names[1..];
+            if flags == "-" {
+                break;
+            }
+            for c in flags.chars() {
+                match c {
+                    'a' => all = true,
+                    't' => kind = true,
+                    'p' => path = true,
+                    'P' => force_path = true,
+                    _ => {
+                        let _ = writeln!(io.stderr, "type: -{}: invalid option", c);
+                        let _ = writeln!(io.stderr, "type: usage: {}", self.usage());
+                        return 2;
+                    }
+                }
+            }
+        }
+
+        let mut status = 0;
+        for name in names {
+            let mut found = find(name, all);
+            if force_path {
+                found = hash::find_all(name).into_iter().map(|p| Found::File(p, false)).collect();
+                if !all {
+                    found.truncate(1);
+                }
+            }
+            if found.is_empty() {
+                if !kind && !path && !force_path {
+                    let _ = writeln!(io.stderr, "type: {}: not found", name);
+                }
+                status = 1;
+            }
+            for f in found {
+                let _ = match f {
+                    _ if kind => writeln!(io.stdout, "{}", f.kind()),
+                    Found::File(p, _) if path || force_path => writeln!(io.stdout, "{}", p.display()),
+                    _ if path => Ok(()),
+                    _ => writeln!(io.stdout, "{}", f.describe(name)),
+                };
+            }
+        }
+        status
+    }
+}
+
+/// Meaning of a command name
+pub enum Found {
+    Alias(String),
+    Builtin,
+    /// a program and if it's remembered by `hash`
+    File(PathBuf, bool),
+}
+
+impl Found {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Found::Alias(_) => "alias",
+            Found::Builtin => "builtin",
+            Found::File(..) => "file",
+        }
+    }
+
+    /// sentence telling what the name means, as printed by `type`
+    pub fn describe(&self, name: &str) -> String {
+        match self {
+            Found::Alias(value) => format!("{} is aliased to `{}'", name, value),
+            Found::Builtin => format!("{} is a shell builtin", name),
+            Found::File(path, true) => format!("{} is hashed ({})", name, path.display()),
+            Found::File(path, false) => format!("{} is {}", name, path.display()),
+        }
+    }
+}
+
+/// What the name means as a command, in the order the shell tries them.
+/// Unless `all` is set only the meaning which is used is returned.
+pub fn find(name: &str, all: bool) -> Vec<Found> {
+    let mut found = vec![];
+    if let Some(value) = alias::get(name) {
+        found.push(Found::Alias(value));
+    }
+    if customs::lookup(name).is_some() {
+        found.push(Found::Builtin);
+    }
+    if all {
+        found.extend(hash::find_all(name).into_iter().map(|p| Found::File(p, false)));
+    } else if found.is_empty() {
+        if let Some(path) = hash::hashed(name).filter(|p| hash::is_executable(p)) {
+            found.push(Found::File(path, true));
+        } else if let Some(path) = hash::find_all(name).into_iter().next() {
+            found.push(Found::File(path, false));
+        }
+    }
+    if !all {
+        found.truncate(1);
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+
+    #[test]
+    fn test_help_lists_builtins() {
+        let (status, out, _) = run_captured(&Help, "help");
+        assert_eq!(0, status);
+        assert!(out.contains("\n  cd [-L|-P] [dir]\n"));
+        assert!(out.contains("\n  type [-aptP] name [name ...]\n"));
+
+        let (status, out, _) = run_captured(&Help, "help let");
+        assert_eq!(0, status);
+        assert!(out.starts_with("let: let arg [arg ...]\n"));
+        assert_eq!(1, run_captured(&Help, "help no_such_builtin").0);
+    }
+
+    #[test]
+    fn test_type() {
+        let (status, out, err) = run_captured(&Type, "type cd sh no_such_command_x");
+        assert_eq!(1, status);
+        assert!(out.starts_with("cd is a shell builtin\nsh is /"));
+        assert_eq!("type: no_such_command_x: not found\n", err);
+
+        let (status, out, _) = run_captured(&Type, "type -t cd sh");
+        assert_eq!(0, status);
+        assert_eq!("builtin\nfile\n", out);
+        let (_, out, _) = run_captured(&Type, "type -p cd");
+        assert_eq!("", out);
+        let (_, out, _) = run_captured(&Type, "type -a -P sh");
+        assert!(out.lines().all(|l| l.ends_with("/sh")));
+    }
+}
@@ -1,8 +1,147 @@
-#![allow(dead_code)]
-use chrono::{DateTime, Utc};
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::vars;
+use chrono::{DateTime, Local};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// number of lines kept in the history file unless `HISTSIZE` says otherwise
+const HISTSIZE: usize = 500;
 
 struct HistEntry {
     line: String,
-    timestamp: DateTime<Utc>,
-    
-}
\ No newline at end of file
+    timestamp: DateTime<Local>,
+}
+
+lazy_static! {
+    /// Lines entered by the user, the oldest first
+    static ref HISTORY: Mutex<Vec<HistEntry>> = Mutex::new(vec![]);
+}
+
+/// Record a line entered by the user
+pub fn add(line: &str) {
+    HISTORY.lock().unwrap().push(HistEntry {
+        line: line.to_string(),
+        timestamp: Local::now(),
+    });
+}
+
+/// Read the lines saved by earlier sessions
+pub fn load() {
+    let contents = match history_file().map(fs::read_to_string) {
+        Some(Ok(c)) => c,
+        _ => return,
+    };
+    for line in contents.lines().filter(|l| !l.is_empty()) {
+        add(line);
+    }
+}
+
+/// Write the last `HISTSIZE` lines to the history file
+pub fn save() -> io::Result<()> {
+    let path = match history_file() {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    let size = vars::get("HISTSIZE")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(HISTSIZE);
+    let history = HISTORY.lock().unwrap();
+    let contents: String = history
+        .iter()
+        .skip(history.len().saturating_sub(size))
+        .map(|e| format!("{}\n", e.line))
+        .collect();
+    fs::write(path, contents)
+}
+
+/// `HISTFILE`, or `.cr4sh_history` in the home directory
+fn history_file() -> Option<PathBuf> {
+    match vars::get("HISTFILE") {
+        Some(f) if f.is_empty() => None,
+        Some(f) => Some(PathBuf::from(f)),
+        None => dirs::home_dir().map(|h| h.join(".cr4sh_history")),
+    }
+}
+
+/// lines of the history, the oldest first
+pub fn lines() -> Vec<String> {
+    HISTORY.lock().unwrap().iter().map(|e| e.line.clone()).collect()
+}
+
+/// Implementation of the `history` command. Prints numbered lines
+/// entered by the user, `history N` prints only the last N lines,
+/// `-t` adds the time of each line and `-c` clears the history.
+pub struct History;
+
+impl Builtin for History {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn help(&self) -> &'static str {
+        "Display or clear the history list."
+    }
+
+    fn usage(&self) -> &'static str {
+        "history [-c] [-t] [n]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        history(&args[1..], io)
+    }
+}
+
+fn history(args: &[String], io: &mut Io) -> ExitStatus {
+    let mut count = usize::MAX;
+    let mut with_time = false;
+    for arg in args {
+        match arg.as_str() {
+            "-c" => {
+                HISTORY.lock().unwrap().clear();
+                return 0;
+            }
+            "-t" => with_time = true,
+            n => match n.parse() {
+                Ok(n) => count = n,
+                Err(_) => {
+                    let _ = writeln!(io.stderr, "history: {}: numeric argument required", n);
+                    return 1;
+                }
+            },
+        }
+    }
+
+    let history = HISTORY.lock().unwrap();
+    let skip = history.len().saturating_sub(count);
+    for (i, entry) in history.iter().enumerate().skip(skip) {
+        let res = if with_time {
+            writeln!(io.stdout, "{:>5}  {}  {}", i + 1,
+                     entry.timestamp.format("%F %T"), entry.line)
+        } else {
+            writeln!(io.stdout, "{:>5}  {}", i + 1, entry.line)
+        };
+        if res.is_err() {
+            return 1;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+
+    #[test]
+    fn test_history_listing() {
+        add("history test first");
+        add("history test second");
+
+        let (status, out, _) = run_captured(&History, "history 1");
+        assert_eq!(0, status);
+        assert!(out.ends_with("  history test second\n"));
+        assert_eq!(1, out.lines().count());
+    }
+}
@@ -0,0 +1,328 @@
+use crate::customs::cd::change_directory;
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::vars;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// When ranks of all directories add up to more than this,
+/// they are aged so rarely visited directories drop out.
+const MAX_RANK: f64 = 9000.0;
+
+/// Implementation of the `z` command, which jumps to the most frecent
+/// directory matching all the patterns. Directories are ranked by how
+/// often and how recently they were entered with `cd`.
+pub struct Z;
+
+impl Builtin for Z {
+    fn name(&self) -> &'static str {
+        "z"
+    }
+
+    fn help(&self) -> &'static str {
+        "Jump to the most frequently and recently used directory matching \
+         the patterns, `-l` lists the matches with their scores."
+    }
+
+    fn usage(&self) -> &'static str {
+        "z [-l] [pattern ...]"
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let list = args.get(1).map(|a| a == "-l").unwrap_or(false);
+        let patterns = &args[if list { 2 } else { 1 }..];
+        let matches = Database::load(&data_file()).matches(patterns, now());
+
+        if list || patterns.is_empty() {
+            for (score, dir) in matches.iter().rev() {
+                let _ = writeln!(io.stdout, "{:<10.1} {}", score, dir);
+            }
+            return 0;
+        }
+        match matches.first() {
+            Some((_, dir)) => jump(ctx, dir, io),
+            None => {
+                let _ = writeln!(io.stderr, "z: no match for {}", patterns.join(" "));
+                1
+            }
+        }
+    }
+}
+
+/// Implementation of the `zi` command, which lists directories
+/// matching the patterns and jumps to the one the user selects.
+pub struct Zi;
+
+impl Builtin for Zi {
+    fn name(&self) -> &'static str {
+        "zi"
+    }
+
+    fn help(&self) -> &'static str {
+        "Select one of the directories matching the patterns and jump to it."
+    }
+
+    fn usage(&self) -> &'static str {
+        "zi [pattern ...]"
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let matches = Database::load(&data_file()).matches(&args[1..], now());
+        if matches.is_empty() {
+            let _ = writeln!(io.stderr, "zi: no match for {}", args[1..].join(" "));
+            return 1;
+        }
+
+        for (i, (_, dir)) in matches.iter().enumerate() {
+            let _ = writeln!(io.stderr, "{:>3}  {}", i + 1, dir);
+        }
+        let _ = write!(io.stderr, "select: ");
+        let _ = io.stderr.flush();
+
+        let mut choice = String::new();
+        if io.stdin.read_line(&mut choice).unwrap_or(0) == 0 {
+            return 1;
+        }
+        match choice.trim().parse::<usize>() {
+            Ok(n) if n >= 1 && n <= matches.len() => jump(ctx, &matches[n - 1].1, io),
+            _ => {
+                let _ = writeln!(io.stderr, "zi: {}: invalid selection", choice.trim());
+                1
+            }
+        }
+    }
+}
+
+/// Record a visit of the directory, called whenever `cd` enters one
+pub fn record(dir: &str) {
+    if vars::get("HOME").as_deref() == Some(dir) {
+        return;
+    }
+    let path = data_file();
+    let mut db = Database::load(&path);
+    db.add(dir, now());
+    if let Err(e) = db.save(&path) {
+        eprintln!("z: {}: {}", path.display(), e);
+    }
+}
+
+fn jump(ctx: &mut Context, dir: &str, io: &mut Io) -> ExitStatus {
+    match change_directory(Path::new(dir), false) {
+        Ok(pwd) => {
+            ctx.shell_name.set_current_dir(&pwd);
+            record(&pwd);
+            0
+        }
+        Err(e) => {
+            let _ = writeln!(io.stderr, "z: {}: {}", dir, e);
+            1
+        }
+    }
+}
+
+/// Location of the database, `_Z_DATA` overrides the default
+fn data_file() -> PathBuf {
+    if let Some(path) = vars::get("_Z_DATA") {
+        return PathBuf::from(path);
+    }
+    dirs::data_dir()
+        .or_else(dirs::home_dir)
+        .unwrap_or_default()
+        .join("cr4sh_")
+        .join("z")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Visited directory with the number of visits and the last one
+#[derive(Debug, PartialEq)]
+struct Entry {
+    path: String,
+    rank: f64,
+    time: u64,
+}
+
+impl Entry {
+    /// rank weighted by how long ago the directory was visited
+    fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.time);
+        if age < 3600 {
+            self.rank * 4.0
+        } else if age < 86400 {
+            self.rank * 2.0
+        } else if age < 604800 {
+            self.rank / 2.0
+        } else {
+            self.rank / 4.0
+        }
+    }
+}
+
+/// Database of visited directories, stored one `path|rank|time` per line
+#[derive(Debug, Default)]
+struct Database {
+    entries: Vec<Entry>,
+}
+
+impl Database {
+    /// Read the database, a missing or malformed file is an empty one.
+    /// Directories which don't exist anymore are pruned.
+    fn load(path: &Path) -> Self {
+        let contents = fs::read_to_string(path).unwrap_or_default();
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.rsplitn(3, '|');
+                let time = parts.next()?.parse().ok()?;
+                let rank = parts.next()?.parse().ok()?;
+                let path = parts.next()?.to_string();
+                Some(Entry { path, rank, time })
+            })
+            .filter(|e| Path::new(&e.path).is_dir())
+            .collect();
+        Database { entries }
+    }
+
+    /// write the database to a temporary file and move it in place
+    fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|e| format!("{}|{}|{}\n", e.path, e.rank, e.time))
+            .collect();
+        let tmp = path.with_extension(format!("{}", std::process::id()));
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Count a visit of the directory. When the total rank grows too
+    /// big all ranks are aged and directories below 1 are forgotten.
+    fn add(&mut self, dir: &str, now: u64) {
+        match self.entries.iter_mut().find(|e| e.path == dir) {
+            Some(e) => {
+                e.rank += 1.0;
+                e.time = now;
+            }
+            None => self.entries.push(Entry {
+                path: dir.to_string(),
+                rank: 1.0,
+                time: now,
+            }),
+        }
+
+        if self.entries.iter().map(|e| e.rank).sum::<f64>() > MAX_RANK {
+            for e in self.entries.iter_mut() {
+                e.rank *= 0.99;
+            }
+            self.entries.retain(|e| e.rank >= 1.0);
+        }
+    }
+
+    /// Directories containing all the patterns in order, the best first.
+    /// Patterns are case sensitive unless nothing matches that way.
+    fn matches(&self, patterns: &[String], now: u64) -> Vec<(f64, String)> {
+        let mut res = self.find(patterns, now, false);
+        if res.is_empty() {
+            res = self.find(patterns, now, true);
+        }
+        res.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        res
+    }
+
+    fn find(&self, patterns: &[String], now: u64, ignore_case: bool) -> Vec<(f64, String)> {
+        let fold = |s: &str| if ignore_case { s.to_lowercase() } else { s.to_string() };
+        self.entries
+            .iter()
+            .filter(|e| {
+                let path = fold(&e.path);
+                let mut rest = path.as_str();
+                patterns.iter().all(|p| {
+                    // folding may change the length, so the folded pattern is skipped
+                    let p = fold(p);
+                    match rest.find(&p) {
+                        Some(i) => {
+                            rest = &rest[i + p.len()..];
+                            true
+                        }
+                        None => false,
+                    }
+                })
+            })
+            .map(|e| (e.frecency(now), e.path.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db(entries: &[(&str, f64, u64)]) -> Database {
+        Database {
+            entries: entries
+                .iter()
+                .map(|(p, r, t)| Entry { path: p.to_string(), rank: *r, time: *t })
+                .collect(),
+        }
+    }
+
+    fn patterns(p: &[&str]) -> Vec<String> {
+        p.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_recent_beats_frequent() {
+        let now = 1_000_000;
+        let db = db(&[("/src/old", 10.0, now - 700_000), ("/src/new", 2.0, now - 10)]);
+        let m = db.matches(&patterns(&["src"]), now);
+        assert_eq!("/src/new", m[0].1);
+        assert_eq!(8.0, m[0].0);
+        assert_eq!(2.5, m[1].0);
+    }
+
+    #[test]
+    fn test_patterns_in_order() {
+        let db = db(&[("/work/Repo/api", 1.0, 0), ("/api/work", 1.0, 0)]);
+        assert_eq!(vec![(4.0, "/work/Repo/api".to_string())],
+                   db.matches(&patterns(&["work", "api"]), 0));
+        assert_eq!(1, db.matches(&patterns(&["repo"]), 0).len());
+    }
+
+    #[test]
+    fn test_fold_changes_length() {
+        // `İ` is two bytes, but three when lowercased
+        let db = db(&[("/İa/é", 1.0, 0)]);
+        assert_eq!(1, db.matches(&patterns(&["İ", "É"]), 0).len());
+    }
+
+    #[test]
+    fn test_aging() {
+        let mut db = db(&[("/a", MAX_RANK, 0), ("/b", 1.0, 0)]);
+        db.add("/a", 5);
+        assert_eq!(1, db.entries.len());
+        assert_eq!(5, db.entries[0].time);
+        assert!(db.entries[0].rank < MAX_RANK);
+    }
+
+    #[test]
+    fn test_save_and_prune() {
+        let path = std::env::temp_dir().join(format!("cr4sh_z_test_{}", std::process::id()));
+        let tmp = std::env::temp_dir().to_string_lossy().to_string();
+        let mut db = db(&[("/no/such/dir/x", 3.0, 7)]);
+        db.add(&tmp, 42);
+        db.save(&path).unwrap();
+
+        let loaded = Database::load(&path);
+        assert_eq!(vec![Entry { path: tmp, rank: 1.0, time: 42 }], loaded.entries);
+        fs::remove_file(path).unwrap();
+    }
+}
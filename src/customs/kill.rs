@@ -0,0 +1,152 @@
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::signals;
+use crate::sys::io_err;
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+use std::convert::TryFrom;
+
+/// Implementation of the `kill` command, which sends a signal, `TERM`
+/// unless another one is given, to processes. A negative pid sends it
+/// to the whole process group and `-l` lists the signal names.
+pub struct Kill;
+
+impl Builtin for Kill {
+    fn name(&self) -> &'static str {
+        "kill"
+    }
+
+    fn help(&self) -> &'static str {
+        "Send a signal to processes or process groups, given by a negative \
+         pid. `-l` lists signal names or translates exit statuses."
+    }
+
+    fn usage(&self) -> &'static str {
+        "kill [-s sigspec | -n signum | -sigspec] pid ... or kill -l [sigspec]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let mut sig = Some(Signal::SIGTERM);
+        let mut rest = &args[1..];
+        match rest.first().map(|a| a.as_str()) {
+            Some("-l") | Some("-L") => return list(&rest[1..], io),
+            Some("-s") | Some("-n") if rest.len() > 1 => {
+                sig = match parse_signal(&rest[1]) {
+                    Ok(s) => s,
+                    Err(()) => return invalid_signal(&rest[1], io),
+                };
+                rest = &rest[2..];
+            }
+            Some("--") => rest = &rest[1..],
+            Some(a) if a.starts_with('-') && a.len() > 1 => {
+                sig = match parse_signal(&a[1..]) {
+                    Ok(s) => s,
+                    Err(()) => return invalid_signal(&a[1..], io),
+                };
+                rest = &rest[1..];
+            }
+            _ => {}
+        }
+        if rest.first().map(|a| a == "--").unwrap_or(false) {
+            rest = &rest[1..];
+        }
+        if rest.is_empty() {
+            let _ = writeln!(io.stderr, "kill: usage: {}", self.usage());
+            return 2;
+        }
+
+        let mut status = 0;
+        for target in rest {
+            if let Err(e) = send(target, sig) {
+                let _ = writeln!(io.stderr, "kill: {}: {}", target, e);
+                status = 1;
+            }
+        }
+        status
+    }
+}
+
+/// Send the signal to a pid or a process group given by a negative
+/// number. `None` only checks the process exists.
+fn send(target: &str, sig: Option<Signal>) -> Result<(), String> {
+    if target.starts_with('%') {
+        return Err("job control not supported".to_string());
+    }
+    let pid: i32 = target
+        .parse()
+        .map_err(|_| "arguments must be process IDs".to_string())?;
+    signal::kill(Pid::from_raw(pid), sig).map_err(|e| io_err(e).to_string())
+}
+
+/// Signal by its name or number, `Ok(None)` is signal 0
+fn parse_signal(spec: &str) -> Result<Option<Signal>, ()> {
+    match signals::parse(spec) {
+        Some(0) => Ok(None),
+        Some(n) => Signal::try_from(n).map(Some).map_err(|_| ()),
+        None => Err(()),
+    }
+}
+
+fn invalid_signal(spec: &str, io: &mut Io) -> ExitStatus {
+    let _ = writeln!(io.stderr, "kill: {}: invalid signal specification", spec);
+    1
+}
+
+/// Print all signals, or translate signal names to numbers and
+/// numbers or exit statuses of killed commands to names
+fn list(specs: &[String], io: &mut Io) -> ExitStatus {
+    if specs.is_empty() {
+        for (i, sig) in Signal::iterator().enumerate() {
+            let sep = if i % 5 == 4 { "\n" } else { "\t" };
+            let _ = write!(io.stdout, "{:>2}) {}{}", sig as i32, sig.as_str(), sep);
+        }
+        let _ = writeln!(io.stdout);
+        return 0;
+    }
+
+    let mut status = 0;
+    for spec in specs {
+        let number = spec.parse::<i32>().ok().map(|n| if n > 128 { n - 128 } else { n });
+        match (number, parse_signal(spec)) {
+            (Some(n), _) => match Signal::try_from(n) {
+                Ok(sig) => {
+                    let _ = writeln!(io.stdout, "{}", &sig.as_str()[3..]);
+                }
+                Err(_) => status = invalid_signal(spec, io),
+            },
+            (None, Ok(Some(sig))) => {
+                let _ = writeln!(io.stdout, "{}", sig as i32);
+            }
+            _ => status = invalid_signal(spec, io),
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+
+    #[test]
+    fn test_list() {
+        let (status, out, _) = run_captured(&Kill, "kill -l INT sigterm 137 9");
+        assert_eq!(0, status);
+        assert_eq!("2\n15\nKILL\nKILL\n", out);
+        let (_, out, _) = run_captured(&Kill, "kill -l");
+        assert!(out.starts_with(" 1) SIGHUP\t 2) SIGINT\t"));
+    }
+
+    #[test]
+    fn test_send() {
+        let pid = std::process::id();
+        assert_eq!(0, run_captured(&Kill, &format!("kill -0 {}", pid)).0);
+        assert_eq!(0, run_captured(&Kill, &format!("kill -s 0 -- {}", pid)).0);
+
+        let (status, _, err) = run_captured(&Kill, "kill -NOPE 1 %2 x");
+        assert_eq!(1, status);
+        assert_eq!("kill: NOPE: invalid signal specification\n", err);
+        let (status, _, err) = run_captured(&Kill, "kill -0 %2 x");
+        assert_eq!(1, status);
+        assert_eq!("kill: %2: job control not supported\nkill: x: arguments must be process IDs\n", err);
+    }
+}
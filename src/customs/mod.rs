@@ -1,4 +1,188 @@
+pub mod abbr;
+pub mod alias;
+pub mod arithmetic;
 pub mod cd;
+pub mod colon;
+pub mod command;
+pub mod dirstack;
+pub mod exec;
+pub mod exit;
+pub mod hash;
+pub mod help;
 pub mod touch;
+pub mod trap;
 pub mod history;
-pub mod source;
\ No newline at end of file
+pub mod jump;
+pub mod kill;
+pub mod read;
+pub mod set;
+pub mod source;
+
+use crate::shellname::ShellName;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+/// Exit status of a command, 0 means success
+pub type ExitStatus = i32;
+
+/// Command implemented by the shell itself
+pub trait Builtin: Sync {
+    /// name the command is invoked by
+    fn name(&self) -> &'static str;
+
+    /// short description printed by `help`
+    fn help(&self) -> &'static str;
+
+    /// synopsis of the arguments, starting with the name
+    fn usage(&self) -> &'static str;
+
+    /// Run the command, `args[0]` is the name it was invoked by
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus;
+}
+
+/// State of the shell builtins can change
+pub struct Context<'a> {
+    pub shell_name: &'a mut ShellName,
+    /// set by a builtin whose redirections should stay in effect
+    /// after it finished, like those of `exec` without a command
+    pub keep_redirections: bool,
+}
+
+lazy_static! {
+    /// Registry of all builtins by their name
+    static ref BUILTINS: BTreeMap<&'static str, &'static dyn Builtin> = {
+        let builtins: [&'static dyn Builtin; 27] = [
+            &colon::Colon,
+            &abbr::Abbr,
+            &alias::Alias,
+            &alias::Unalias,
+            &cd::Cd,
+            &command::Command,
+            &dirstack::Dirs,
+            &dirstack::Popd,
+            &dirstack::Pushd,
+            &exec::Exec,
+            &exit::Exit("exit"),
+            &hash::Hash,
+            &help::Help,
+            &help::Type,
+            &history::History,
+            &kill::Kill,
+            &arithmetic::Let,
+            &exit::Exit("logout"),
+            &cd::Pwd,
+            &read::Read,
+            &set::Set,
+            &source::Source("source"),
+            &source::Source("."),
+            &touch::Touch,
+            &trap::Trap,
+            &jump::Z,
+            &jump::Zi,
+        ];
+        builtins.iter().map(|b| (b.name(), *b)).collect()
+    };
+}
+
+/// find the builtin with the given name
+pub fn lookup(name: &str) -> Option<&'static dyn Builtin> {
+    BUILTINS.get(name).copied()
+}
+
+/// all registered builtins ordered by name
+pub fn builtins() -> impl Iterator<Item = &'static dyn Builtin> {
+    BUILTINS.values().copied()
+}
+
+/// Standard streams a builtin reads from and writes to. Builtins never
+/// use `println!` directly so their output can go to a pipe, a file or
+/// a buffer in tests the same as output of external programs.
+pub struct Io<'a> {
+    pub stdin: &'a mut dyn BufRead,
+    pub stdout: &'a mut dyn Write,
+    pub stderr: &'a mut dyn Write,
+}
+
+/// Run `f` with streams of the shell process,
+/// which might have been redirected beforehand
+pub fn with_std_io<T>(f: impl FnOnce(&mut Io) -> T) -> T {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let stderr = io::stderr();
+    let mut io = Io {
+        stdin: &mut stdin.lock(),
+        stdout: &mut stdout.lock(),
+        stderr: &mut stderr.lock(),
+    };
+    let res = f(&mut io);
+    let _ = io.stdout.flush();
+    res
+}
+
+/// split the line into arguments like the shell does
+#[cfg(test)]
+pub fn args(line: &str) -> Vec<String> {
+    crate::tokenizer::Tokenizer::new(line).collect()
+}
+
+/// Run `f` with empty input and return what it wrote to stdout and stderr
+#[cfg(test)]
+pub fn captured<T>(f: impl FnOnce(&mut Io) -> T) -> (T, String, String) {
+    let mut out = vec![];
+    let mut err = vec![];
+    let res = f(&mut Io {
+        stdin: &mut &b""[..],
+        stdout: &mut out,
+        stderr: &mut err,
+    });
+    (
+        res,
+        String::from_utf8(out).unwrap(),
+        String::from_utf8(err).unwrap(),
+    )
+}
+
+/// Run the builtin with arguments of the line and return its status
+/// along with what it wrote to stdout and stderr
+#[cfg(test)]
+pub fn run_captured(builtin: &dyn Builtin, line: &str) -> (ExitStatus, String, String) {
+    let mut shell_name = ShellName::new("test");
+    let mut ctx = Context { shell_name: &mut shell_name, keep_redirections: false };
+    captured(|io| builtin.run(&mut ctx, &args(line), io))
+}
+
+#[cfg(test)]
+lazy_static! {
+    /// Held by tests which change the working directory of the process
+    static ref CWD: std::sync::Mutex<()> = std::sync::Mutex::new(());
+}
+
+/// Run `f` in a new scratch directory, which is passed to it. The working
+/// directory, `PWD` and `OLDPWD` are restored afterwards and directories
+/// visited by `cd` aren't recorded in the user's `z` database.
+#[cfg(test)]
+pub fn in_scratch_dir(name: &str, f: impl FnOnce(&str)) {
+    use crate::vars;
+    use std::{env, fs};
+
+    let _guard = CWD.lock().unwrap_or_else(|e| e.into_inner());
+    let base = env::temp_dir().canonicalize().unwrap();
+    let dir = base.join(format!("cr4sh_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let saved = (env::current_dir().unwrap(), vars::get("PWD"), vars::get("OLDPWD"));
+    vars::export("_Z_DATA", &dir.join(".z").to_string_lossy());
+    env::set_current_dir(&dir).unwrap();
+    vars::export("PWD", &dir.to_string_lossy());
+
+    f(&dir.to_string_lossy());
+
+    env::set_current_dir(&saved.0).unwrap();
+    for (name, value) in [("PWD", saved.1), ("OLDPWD", saved.2)].iter() {
+        match value {
+            Some(v) => vars::export(name, v),
+            None => vars::unset(name),
+        }
+    }
+    vars::unset("_Z_DATA");
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,413 @@
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::sys::{self, io_err};
+use crate::vars;
+use nix::poll::{poll, PollFd, PollFlags};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use termion::raw::IntoRawMode;
+
+/// Status of `read` when the time given by `-t` ran out, like
+/// for a command killed by `SIGALRM`
+const TIMEOUT: ExitStatus = 128 + 14;
+
+/// Implementation of the `read` command. It reads a line, splits it into
+/// fields on `IFS` and assigns them to the variables, the last one gets
+/// the rest of the line. Without names the whole line is put in `REPLY`,
+/// with `-a` each field becomes an element of the array. Backslashes
+/// escape the next character unless `-r` is given.
+pub struct Read;
+
+/// How the input is read
+#[derive(Debug, PartialEq)]
+struct Options {
+    /// `-r` keeps backslashes as they are
+    raw: bool,
+    /// `-s` doesn't echo what is typed on a terminal
+    silent: bool,
+    /// `-p` prompt printed to a terminal
+    prompt: Option<String>,
+    /// `-t` seconds to wait for the input
+    timeout: Option<f64>,
+    /// `-n` reads at most this many characters
+    chars: Option<usize>,
+    /// `-d` ends the input instead of a newline
+    delim: u8,
+    /// `-a` array which gets all the fields
+    array: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { raw: false, silent: false, prompt: None, timeout: None, chars: None, delim: b'\n', array: None }
+    }
+}
+
+/// Why reading the input stopped
+#[derive(Debug, PartialEq)]
+enum End {
+    /// the delimiter or enough characters were read
+    Done,
+    Eof,
+    Timeout,
+    /// Ctrl-C was typed in raw mode
+    Interrupted,
+}
+
+impl Builtin for Read {
+    fn name(&self) -> &'static str {
+        "read"
+    }
+
+    fn help(&self) -> &'static str {
+        "Read a line and split it into fields assigned to the variables. \
+         `-p` prints a prompt, `-s` hides the input, `-t` gives up after \
+         some seconds, `-n` reads only N characters, `-d` up to a delimiter \
+         and `-a` puts the fields in the array elements `name[0]`, `name[1]`, ..."
+    }
+
+    fn usage(&self) -> &'static str {
+        "read [-rs] [-p prompt] [-t secs] [-n N] [-d delim] [-a array] [name ...]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let (opts, names) = match parse_options(&args[1..]) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let _ = writeln!(io.stderr, "read: {}", e);
+                let _ = writeln!(io.stderr, "read: usage: {}", self.usage());
+                return 2;
+            }
+        };
+        if let Some(name) = names.iter().chain(&opts.array).find(|n| !vars::is_valid_name(n)) {
+            let _ = writeln!(io.stderr, "read: `{}': not a valid identifier", name);
+            return 1;
+        }
+
+        let tty = termion::is_tty(&io::stdin());
+        if let Some(prompt) = opts.prompt.as_ref().filter(|_| tty) {
+            let _ = write!(io.stderr, "{}", prompt);
+            let _ = io.stderr.flush();
+        }
+        // `-t 0` only tells if there is input waiting
+        if opts.timeout == Some(0.0) {
+            return match wait_input(Some(Instant::now())) {
+                Ok(true) => 0,
+                _ => 1,
+            };
+        }
+
+        // the descriptor is read directly, so only what's assigned is consumed
+        let mut next = || sys::read_byte(0);
+        let result = if tty && (opts.silent || opts.chars.is_some()) {
+            read_raw(&opts, &mut next)
+        } else {
+            read_input(&opts, &mut next, None)
+        };
+        match result {
+            Ok((input, end)) => finish(&opts, names, &input, end),
+            Err(e) => {
+                let _ = writeln!(io.stderr, "read: {}", e);
+                1
+            }
+        }
+    }
+}
+
+/// Assign what was read and return the status for the way reading ended
+fn finish(opts: &Options, names: &[String], input: &[(u8, bool)], end: End) -> ExitStatus {
+    if end == End::Interrupted {
+        return 130;
+    }
+    assign(opts.array.as_deref(), names, input);
+    match end {
+        End::Done => 0,
+        End::Eof => 1,
+        _ => TIMEOUT,
+    }
+}
+
+/// Split the options of `read` from the names of the variables
+fn parse_options(args: &[String]) -> Result<(Options, &[String]), String> {
+    let mut opts = Options::default();
+    let mut rest = args;
+    while let Some(flags) = rest.first().and_then(|a| a.strip_prefix('-')) {
+        if flags.is_empty() {
+            break;
+        }
+        rest = &rest[1..];
+        if flags == "-" {
+            break;
+        }
+        for (i, c) in flags.char_indices() {
+            if c == 'r' || c == 's' {
+                opts.raw |= c == 'r';
+                opts.silent |= c == 's';
+                continue;
+            }
+            if !"ptnda".contains(c) {
+                return Err(format!("-{}: invalid option", c));
+            }
+            // the value is the rest of the word or the next argument
+            let value = match &flags[i + c.len_utf8()..] {
+                "" => {
+                    let value = rest
+                        .first()
+                        .ok_or_else(|| format!("-{}: option requires an argument", c))?;
+                    rest = &rest[1..];
+                    value.clone()
+                }
+                value => value.to_string(),
+            };
+            match c {
+                'p' => opts.prompt = Some(value),
+                't' => match value.parse::<f64>() {
+                    Ok(secs) if secs >= 0.0 => opts.timeout = Some(secs),
+                    _ => return Err(format!("{}: invalid timeout specification", value)),
+                },
+                'n' => match value.parse() {
+                    Ok(n) => opts.chars = Some(n),
+                    Err(_) => return Err(format!("{}: invalid number", value)),
+                },
+                'd' => opts.delim = value.bytes().next().unwrap_or(0),
+                _ => opts.array = Some(value),
+            }
+            break;
+        }
+    }
+    Ok((opts, rest))
+}
+
+/// Read from a terminal switched to raw mode, so characters arrive as
+/// they're typed and aren't echoed. They are echoed here unless `-s`.
+fn read_raw(opts: &Options, next: &mut Source) -> io::Result<(Vec<(u8, bool)>, End)> {
+    let mut tty = termion::get_tty()?.into_raw_mode()?;
+    read_input(opts, next, Some(&mut *tty))
+}
+
+/// Where the input comes from, a byte at a time, `None` at its end
+type Source<'a> = dyn FnMut() -> io::Result<Option<u8>> + 'a;
+
+/// Read the input up to the delimiter, `-n` characters or the end of the
+/// input. Bytes are paired with `true` when escaped by a backslash, those
+/// don't split fields. `echo` is the terminal in raw mode.
+fn read_input(
+    opts: &Options,
+    next: &mut Source,
+    mut echo: Option<&mut dyn Write>,
+) -> io::Result<(Vec<(u8, bool)>, End)> {
+    let deadline = opts.timeout.map(|t| Instant::now() + Duration::from_secs_f64(t));
+    let mut input = vec![];
+    let mut escaped = false;
+    // only whole characters are counted for `-n`
+    let mut chars = 0;
+    let mut continuation = 0;
+
+    loop {
+        if opts.chars == Some(chars) && continuation == 0 {
+            return Ok((input, End::Done));
+        }
+        // the time can run out while the input keeps coming
+        if deadline.is_some_and(|d| Instant::now() >= d) || !wait_input(deadline)? {
+            return Ok((input, End::Timeout));
+        }
+        let mut byte = match next()? {
+            Some(b) => b,
+            None => return Ok((input, End::Eof)),
+        };
+        if byte & 0xc0 == 0x80 {
+            continuation -= 1.min(continuation);
+        } else {
+            chars += 1;
+            continuation = match byte {
+                0xf0..=0xff => 3,
+                0xe0..=0xef => 2,
+                0xc0..=0xdf => 1,
+                _ => 0,
+            };
+        }
+
+        if let Some(tty) = echo.as_mut() {
+            match byte {
+                3 => return Ok((input, End::Interrupted)),
+                4 if input.is_empty() => return Ok((input, End::Eof)),
+                b'\r' => byte = b'\n',
+                _ => {}
+            }
+            if !opts.silent {
+                let _ = tty.write_all(if byte == b'\n' { b"\r\n" } else { std::slice::from_ref(&byte) });
+                let _ = tty.flush();
+            }
+        }
+
+        if escaped {
+            escaped = false;
+            // an escaped newline continues the line
+            if byte != b'\n' {
+                input.push((byte, true));
+            }
+            continue;
+        }
+        if byte == b'\\' && !opts.raw {
+            escaped = true;
+            continue;
+        }
+        if byte == opts.delim {
+            return Ok((input, End::Done));
+        }
+        input.push((byte, false));
+    }
+}
+
+/// Wait until there is input on the standard input, `false` when the
+/// deadline passed. Without a deadline it doesn't wait.
+fn wait_input(deadline: Option<Instant>) -> io::Result<bool> {
+    let deadline = match deadline {
+        Some(d) => d,
+        None => return Ok(true),
+    };
+    let left = deadline.saturating_duration_since(Instant::now());
+    let mut fds = [PollFd::new(0, PollFlags::POLLIN)];
+    match poll(&mut fds, left.as_millis() as i32) {
+        Ok(n) => Ok(n > 0),
+        Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => wait_input(Some(deadline)),
+        Err(e) => Err(io_err(e)),
+    }
+}
+
+/// Assign the fields of the input to the elements of the array or to
+/// the variables, or the whole input to `REPLY`
+fn assign(array: Option<&str>, names: &[String], input: &[(u8, bool)]) {
+    let text = |bytes: &[(u8, bool)]| {
+        String::from_utf8_lossy(&bytes.iter().map(|(b, _)| *b).collect::<Vec<_>>()).into_owned()
+    };
+    let ifs = vars::get("IFS").unwrap_or_else(|| " \t\n".to_string());
+    if let Some(array) = array {
+        let fields: Vec<_> = split(input, ifs.as_bytes(), usize::MAX).into_iter().map(text).collect();
+        vars::set_array(array, &fields);
+        return;
+    }
+    if names.is_empty() {
+        vars::set("REPLY", &text(input));
+        return;
+    }
+
+    let fields = split(input, ifs.as_bytes(), names.len());
+    for (i, name) in names.iter().enumerate() {
+        vars::set(name, &fields.get(i).map(|f| text(f)).unwrap_or_default());
+    }
+}
+
+/// Split the input into at most `count` fields on the `IFS` characters.
+/// Whitespace in `IFS` is trimmed around fields, the last field keeps
+/// the rest of the input with its separators.
+fn split<'a>(input: &'a [(u8, bool)], ifs: &[u8], count: usize) -> Vec<&'a [(u8, bool)]> {
+    let is_sep = |&(b, escaped): &(u8, bool)| !escaped && ifs.contains(&b);
+    let is_space = |c: &(u8, bool)| is_sep(c) && c.0.is_ascii_whitespace();
+    let trim_start = |s: &'a [(u8, bool)]| &s[s.iter().position(|c| !is_space(c)).unwrap_or(s.len())..];
+
+    let mut fields = vec![];
+    let mut rest = trim_start(input);
+    while !rest.is_empty() {
+        if fields.len() + 1 == count {
+            let end = rest.iter().rposition(|c| !is_space(c)).map_or(0, |e| e + 1);
+            fields.push(&rest[..end]);
+            break;
+        }
+        let end = rest.iter().position(is_sep).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = trim_start(&rest[end..]);
+        // a single separator which isn't whitespace ends the field
+        if rest.first().is_some_and(|c| is_sep(c) && !is_space(c)) {
+            rest = trim_start(&rest[1..]);
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::{args, run_captured};
+
+    /// run `read` with the arguments of the line on the input
+    fn read_with(input: &str, line: &str) -> ExitStatus {
+        let words = args(line);
+        let (opts, names) = parse_options(&words[1..]).unwrap();
+        let mut bytes = input.bytes();
+        let (input, end) = read_input(&opts, &mut || Ok(bytes.next()), None).unwrap();
+        finish(&opts, names, &input, end)
+    }
+
+    fn fields(input: &str, ifs: &str, count: usize) -> Vec<String> {
+        let input: Vec<_> = input.bytes().map(|b| (b, false)).collect();
+        split(&input, ifs.as_bytes(), count)
+            .iter()
+            .map(|f| f.iter().map(|(b, _)| *b as char).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_split_fields() {
+        assert_eq!(vec!["a", "b  c"], fields("  a  b  c  ", " \t\n", 2));
+        assert_eq!(vec!["a", "", "c"], fields("a::c", ":", 3));
+        assert_eq!(vec!["a", "b"], fields(" a , b ", " ,", 2));
+        assert!(fields("   ", " ", 2).is_empty());
+    }
+
+    #[test]
+    fn test_read_variables() {
+        assert_eq!(0, read_with("one two  three\nnext\n", "read read_a read_b"));
+        assert_eq!(Some("one".to_string()), vars::get("read_a"));
+        assert_eq!(Some("two  three".to_string()), vars::get("read_b"));
+
+        assert_eq!(0, read_with("a\\ b\\\nc d\n", "read read_a read_b"));
+        assert_eq!(Some("a bc".to_string()), vars::get("read_a"));
+        assert_eq!(0, read_with("a\\ b c\n", "read -r read_a read_b"));
+        assert_eq!(Some("a\\".to_string()), vars::get("read_a"));
+        vars::unset("read_a");
+        vars::unset("read_b");
+    }
+
+    #[test]
+    fn test_delimiters_and_eof() {
+        assert_eq!(0, read_with("héllo", "read -n 2 read_n"));
+        assert_eq!(Some("hé".to_string()), vars::get("read_n"));
+        assert_eq!(0, read_with("a,b\n", "read -d , read_n"));
+        assert_eq!(Some("a".to_string()), vars::get("read_n"));
+        assert_eq!(1, read_with("partial", "read read_n"));
+        assert_eq!(Some("partial".to_string()), vars::get("read_n"));
+        vars::unset("read_n");
+    }
+
+    #[test]
+    fn test_leaves_rest_of_input() {
+        let mut bytes = "l1\nl2\n".bytes();
+        let (input, end) = read_input(&Options::default(), &mut || Ok(bytes.next()), None).unwrap();
+        assert_eq!((2, End::Done), (input.len(), end));
+        assert_eq!("l2\n", bytes.collect::<Vec<_>>().iter().map(|b| *b as char).collect::<String>());
+    }
+
+    #[test]
+    fn test_read_array() {
+        vars::set("read_arr[3]", "stale");
+        assert_eq!(0, read_with("  x y\\ z  w \n", "read -a read_arr"));
+        assert_eq!(vec!["x", "y z", "w"], vars::array("read_arr"));
+        assert_eq!(None, vars::get("read_arr[3]"));
+        vars::set_array("read_arr", &[]);
+    }
+
+    #[test]
+    fn test_timeout_with_endless_input() {
+        let opts = Options { timeout: Some(0.05), ..Options::default() };
+        let (_, end) = read_input(&opts, &mut || Ok(Some(b'0')), None).unwrap();
+        assert_eq!(End::Timeout, end);
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert_eq!(2, run_captured(&Read, "read -t x").0);
+        assert_eq!(2, run_captured(&Read, "read -a").0);
+        assert_eq!(1, run_captured(&Read, "read -a 1x").0);
+        assert_eq!(1, run_captured(&Read, "read 1x").0);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::options;
+
+/// Implementation of the `set` command. `-X` turns the option on and
+/// `+X` turns it off, `-o name`/`+o name` do the same by a long name.
+/// Without a name `-o` lists all options and their state.
+pub struct Set;
+
+impl Builtin for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn help(&self) -> &'static str {
+        "Set or unset shell options. `-f` (noglob) is accepted, but does \
+         nothing as the shell has no pathname expansion."
+    }
+
+    fn usage(&self) -> &'static str {
+        "set [-Cefux] [+Cefux] [-o option-name] [+o option-name]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        set(&args[1..], io)
+    }
+}
+
+fn set(args: &[String], io: &mut Io) -> ExitStatus {
+    let mut args = args.iter();
+    let mut status = 0;
+    while let Some(arg) = args.next() {
+        let on = arg.starts_with('-');
+        if !on && !arg.starts_with('+') {
+            let _ = writeln!(io.stderr, "set: {}: invalid option", arg);
+            return 2;
+        }
+
+        if &arg[1..] == "o" {
+            match args.next() {
+                Some(name) => {
+                    if !options::set(name, on) {
+                        let _ = writeln!(io.stderr, "set: {}: invalid option name", name);
+                        status = 1;
+                    }
+                }
+                None => print_options(on, io),
+            }
+            continue;
+        }
+
+        for c in arg[1..].chars() {
+            match options::by_letter(c) {
+                Some(name) => {
+                    options::set(name, on);
+                }
+                None => {
+                    let _ = writeln!(io.stderr, "set: {}{}: invalid option", &arg[..1], c);
+                    return 2;
+                }
+            }
+        }
+    }
+    status
+}
+
+/// `set -o` prints a table of options, `set +o` prints
+/// commands which would restore the current settings
+fn print_options(human: bool, io: &mut Io) {
+    for (_, name) in options::OPTIONS.iter() {
+        let on = options::is_set(name);
+        if human {
+            let _ = writeln!(io.stdout, "{:<15}\t{}", name, if on { "on" } else { "off" });
+        } else {
+            let _ = writeln!(io.stdout, "set {}o {}", if on { '-' } else { '+' }, name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+
+    fn run(line: &str) -> i32 {
+        run_captured(&Set, line).0
+    }
+
+    #[test]
+    fn test_noclobber_flag() {
+        assert_eq!(0, run("set -C"));
+        assert!(options::is_set("noclobber"));
+        assert_eq!(0, run("set +o noclobber"));
+        assert!(!options::is_set("noclobber"));
+        assert_eq!(2, run("set -Q"));
+        assert_eq!(1, run("set -o nosuchoption"));
+    }
+
+    #[test]
+    fn test_combined_flags() {
+        assert_eq!(0, run("set -xf -o pipefail"));
+        assert!(options::is_set("xtrace") && options::is_set("noglob"));
+        assert!(options::flags().contains("fx"));
+        assert_eq!(0, run("set +xf +o pipefail"));
+        assert!(!options::is_set("xtrace") && !options::is_set("pipefail"));
+    }
+
+    #[test]
+    fn test_list_options() {
+        let (status, out, _) = run_captured(&Set, "set +o");
+        assert_eq!(0, status);
+        assert!(out.lines().any(|l| l.ends_with("o noclobber")));
+    }
+}
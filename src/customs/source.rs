@@ -1,6 +1,96 @@
-#![allow(dead_code)]
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::heredoc;
+use crate::signals;
+use crate::tokenizer::Tokenizer;
+use std::fs;
 
-pub enum NativeFn {
-    Source,
-    
+/// Implementation of the `source` command and its `.` synonym, which
+/// execute commands from a file in the current shell. Registered once
+/// for each name.
+pub struct Source(pub &'static str);
+
+impl Builtin for Source {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn help(&self) -> &'static str {
+        "Execute commands from a file in the current shell."
+    }
+
+    fn usage(&self) -> &'static str {
+        if self.0 == "." {
+            ". filename"
+        } else {
+            "source filename"
+        }
+    }
+
+    fn run(&self, ctx: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let path = match args.get(1) {
+            Some(p) => p,
+            None => {
+                let _ = writeln!(io.stderr, "{}: filename argument required", self.0);
+                return 2;
+            }
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => {
+                let _ = writeln!(io.stderr, "{}: {}: {}", self.0, path, e);
+                return 1;
+            }
+        };
+        let _ = io.stdout.flush();
+        let status = source(ctx, &contents);
+        signals::run_trap(ctx.shell_name, signals::RETURN);
+        status
+    }
+}
+
+/// Run every line of the script, lines following a here-document
+/// operator are its body. Returns the status of the last command.
+fn source(ctx: &mut Context, script: &str) -> ExitStatus {
+    let mut lines = script.lines().map(|l| l.to_string());
+    let mut status = 0;
+    while let Some(line) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = match heredoc::collect_heredocs(line, &mut || lines.next()) {
+            Ok(l) => l,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return 1;
+            }
+        };
+        status = crate::run_line(ctx.shell_name, &mut Tokenizer::new(&line));
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+    use crate::vars;
+
+    #[test]
+    fn test_source_file() {
+        let name = "source_test_script.sh";
+        fs::write(name, "# comment\n\nsource_x=1\nlet source_x+=2\n").unwrap();
+        assert_eq!(0, run_captured(&Source("."), &format!(". {}", name)).0);
+        assert_eq!(Some("3".to_string()), vars::get("source_x"));
+        fs::remove_file(name).unwrap();
+        vars::unset("source_x");
+    }
+
+    #[test]
+    fn test_missing_file() {
+        let (status, _, err) = run_captured(&Source("source"), "source no_such_script.sh");
+        assert_eq!(1, status);
+        assert!(err.starts_with("source: no_such_script.sh: "));
+        assert_eq!(2, run_captured(&Source("source"), "source").0);
+    }
 }
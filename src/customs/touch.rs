@@ -1,117 +1,395 @@
-use crate::Tokenizer;
-use fs_set_times::{set_atime, set_mtime, SystemTimeSpec};
-use std::collections::HashSet;
-use std::io::{ErrorKind, Result};
-use std::path::Path;
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+use fs_set_times::{set_symlink_times, set_times, SystemTimeSpec};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
 use std::time::SystemTime;
-use std::{fs, io};
-
-pub fn touch(tokenizer: &mut Tokenizer) -> Result<()> {
-    let cmd = parse_command(tokenizer).unwrap();
-    let mut create_flag = true;
-
-    let mut newfile_index = 0usize;
-    let mut reffile_index = 0usize;
-    let mut options_index = usize::MAX;
-    for (i, val) in cmd.iter().enumerate() {
-        if val.starts_with('-') {
-            options_index = i;
-            if cmd[options_index].contains('r') {
-                if cmd.len() > i + 1 {
-                    reffile_index = i + 1;
-                } else {
-                    return Err(io::Error::new(
-                                ErrorKind::InvalidInput, "Invalid argument"
-                            ));
-                }
-                if cmd.len() > i + 2 {
-                    newfile_index = i + 2;
-                } else {
-                    newfile_index = i - 1
+
+/// Implementation of the `touch` command, which updates access and
+/// modification times of files and creates the missing ones.
+pub struct Touch;
+
+impl Builtin for Touch {
+    fn name(&self) -> &'static str {
+        "touch"
+    }
+
+    fn help(&self) -> &'static str {
+        "Change file timestamps, creating files which don't exist. The time \
+         is the current one unless given by `-d`, `-t` or a reference file."
+    }
+
+    fn usage(&self) -> &'static str {
+        "touch [-achm] [-d date] [-r ref_file] [-t [[CC]YY]MMDDhhmm[.ss]] file ..."
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        touch(args, io)
+    }
+}
+
+/// What `touch` changes and how, as given by its options
+#[derive(Debug)]
+struct Settings {
+    access: bool,
+    modify: bool,
+    create: bool,
+    /// with `-h` symbolic links are changed instead of their targets
+    dereference: bool,
+    /// access and modification time, the current time when not given
+    times: Option<(SystemTime, SystemTime)>,
+}
+
+/// Long options with the short option each one stands for,
+/// `--time` has no short form and is handled on its own
+const LONG_OPTIONS: &[(&str, Option<char>)] = &[
+    ("date", Some('d')),
+    ("no-create", Some('c')),
+    ("no-dereference", Some('h')),
+    ("reference", Some('r')),
+    ("time", None),
+];
+
+/// short options which take an argument
+const WITH_ARGUMENT: &str = "drt";
+
+fn touch(args: &[String], io: &mut Io) -> ExitStatus {
+    let (settings, files) = match parse_options(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let _ = writeln!(io.stderr, "touch: {}", e);
+            return 1;
+        }
+    };
+    if files.is_empty() {
+        let _ = writeln!(io.stderr, "touch: missing file operand");
+        return 1;
+    }
+
+    // a file which can't be touched doesn't stop the others
+    let mut status = 0;
+    for f in files.iter() {
+        if let Err(e) = touch_file(f, &settings) {
+            let _ = writeln!(io.stderr, "touch: {}", e);
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Parse the arguments into settings and files. Options may come
+/// after files and short ones may be grouped, with the argument of the
+/// last one attached or in the next word. `--` ends the options and
+/// long options may be abbreviated to any unambiguous prefix.
+fn parse_options(args: &[String]) -> Result<(Settings, Vec<String>)> {
+    let mut parser = Parser {
+        settings: Settings {
+            access: false,
+            modify: false,
+            create: true,
+            dereference: true,
+            times: None,
+        },
+        sources: 0,
+        reference: None,
+    };
+    let mut files = vec![];
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            files.extend(args.cloned());
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((n, v)) => (n, Some(v.to_string())),
+                None => (long, None),
+            };
+            let (name, short) = long_option(name)?;
+            let value = match (short.is_none_or(|c| WITH_ARGUMENT.contains(c)), value) {
+                (true, Some(v)) => Some(v),
+                (true, None) => Some(args.next().cloned().ok_or_else(|| {
+                    invalid(format!("option '--{}' requires an argument", name))
+                })?),
+                (false, Some(_)) => {
+                    return Err(invalid(format!("option '--{}' doesn't allow an argument", name)))
                 }
-            } else {
-                newfile_index = i + 1;
+                (false, None) => None,
+            };
+
+            match (short, value) {
+                (Some(c), value) => parser.option(c, value)?,
+                (None, Some(word)) => match word.as_str() {
+                    "access" | "atime" | "use" => parser.option('a', None)?,
+                    "modify" | "mtime" => parser.option('m', None)?,
+                    _ => return Err(invalid(format!("invalid argument '{}' for '--time'", word))),
+                },
+                (None, None) => unreachable!(),
             }
+            continue;
+        }
+
+        let flags = match arg.strip_prefix('-') {
+            Some(f) if !f.is_empty() => f,
+            _ => {
+                files.push(arg.clone());
+                continue;
+            }
+        };
+        for (i, c) in flags.char_indices() {
+            if !WITH_ARGUMENT.contains(c) {
+                parser.option(c, None)?;
+                continue;
+            }
+            let rest = &flags[i + 1..];
+            let value = if rest.is_empty() {
+                args.next().cloned().ok_or_else(|| {
+                    invalid(format!("option requires an argument -- '{}'", c))
+                })?
+            } else {
+                rest.to_string()
+            };
+            parser.option(c, Some(value))?;
+            break;
         }
     }
 
-    // will need when 'r' flag is implemented
-    let refer = if reffile_index > 0 {
-        get_reference_timestamp(&cmd[reffile_index])
-    } else {
-        None
-    };
+    Ok((parser.finish()?, files))
+}
+
+/// find the long option `name` is an unambiguous prefix of
+fn long_option(name: &str) -> Result<(&'static str, Option<char>)> {
+    if let Some(&(long, short)) = LONG_OPTIONS.iter().find(|(l, _)| *l == name) {
+        return Ok((long, short));
+    }
+    let candidates: Vec<_> = LONG_OPTIONS
+        .iter()
+        .filter(|(l, _)| !name.is_empty() && l.starts_with(name))
+        .collect();
+    match candidates[..] {
+        [&(long, short)] => Ok((long, short)),
+        [] => Err(invalid(format!("unrecognized option '--{}'", name))),
+        _ => Err(invalid(format!("option '--{}' is ambiguous", name))),
+    }
+}
 
-    if options_index < cmd.len() {
-        for op in cmd[options_index].chars().into_iter() {
-            match op {
-                '-' => continue,
-                'c' => create_flag = false,
-                'a' => set_time(&cmd[newfile_index..], &refer,
-                                create_flag, set_atime)?,
-                'm' => set_time(&cmd[newfile_index..], &refer,
-                                create_flag, set_mtime)?,
-                'r' => set_time(&cmd[newfile_index..], &refer,
-                                create_flag, set_mtime)?,
-                _ => eprintln!("{} is invalid operand", op),
+/// Settings collected while parsing options
+struct Parser {
+    settings: Settings,
+    /// number of options giving the time
+    sources: usize,
+    reference: Option<String>,
+}
+
+impl Parser {
+    /// apply the short option `c` with its argument
+    fn option(&mut self, c: char, value: Option<String>) -> Result<()> {
+        let settings = &mut self.settings;
+        match (c, value) {
+            ('a', _) => settings.access = true,
+            ('m', _) => settings.modify = true,
+            ('c', _) => settings.create = false,
+            ('h', _) => settings.dereference = false,
+            ('f', _) => {}
+            ('d', Some(date)) => {
+                settings.times = Some(same(parse_date(&date)?));
+                self.sources += 1;
+            }
+            ('t', Some(stamp)) => {
+                settings.times = Some(same(parse_stamp(&stamp)?));
+                self.sources += 1;
+            }
+            ('r', Some(file)) => {
+                self.reference = Some(file);
+                self.sources += 1;
             }
+            _ => return Err(invalid(format!("invalid option -- '{}'", c))),
+        }
+        Ok(())
+    }
+
+    /// check the combination of options and read the reference file
+    fn finish(mut self) -> Result<Settings> {
+        if self.sources > 1 {
+            return Err(invalid("cannot specify times from more than one source".to_string()));
+        }
+        let settings = &mut self.settings;
+        if let Some(r) = &self.reference {
+            settings.times = Some(reference_times(r, settings.dereference)?);
+        }
+        if !settings.access && !settings.modify {
+            settings.access = true;
+            settings.modify = true;
+        }
+        Ok(self.settings)
+    }
+}
+
+/// Create the file if needed and set its times
+fn touch_file(file: &str, settings: &Settings) -> Result<()> {
+    let exists = fs::symlink_metadata(file).is_ok();
+    if !exists {
+        if !settings.create {
+            return Ok(());
         }
+        if settings.dereference {
+            fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(file)
+                .map_err(|e| context(e, "cannot touch", file))?;
+        }
+    }
+
+    let now = SystemTime::now();
+    let (atime, mtime) = settings.times.unwrap_or((now, now));
+    let atime = Some(SystemTimeSpec::Absolute(atime)).filter(|_| settings.access);
+    let mtime = Some(SystemTimeSpec::Absolute(mtime)).filter(|_| settings.modify);
+
+    if settings.dereference {
+        set_times(file, atime, mtime)
     } else {
-        set_time(&cmd[newfile_index..], &refer, create_flag, set_atime)?;
-        set_time(&cmd[newfile_index..], &refer, create_flag, set_mtime)?;
-    }
-    Ok(())
-}
-
-fn parse_command(tokenizer: &mut Tokenizer) -> Result<Vec<String>> {
-    tokenizer.next();
-    let symbols: HashSet<_> = 
-        vec!["~", "#", "@", "<", ">", "&", "|", ">", "%", "*", "(", ")", "!"]
-            .into_iter()
-            .collect();
-    let res: Vec<_> = tokenizer.filter(|v| !symbols.contains(&v[0..1])).collect();
-    Ok(res)
-}
-
-fn set_time(
-    src: &[String],
-    refer: &Option<SystemTime>,
-    flag: bool,
-    func: fn(path: String, atime: SystemTimeSpec) -> Result<()>,
-) -> Result<()> {
-    let time = if refer.is_some() {
-        refer.unwrap()
+        set_symlink_times(file, atime, mtime)
+    }
+    .map_err(|e| context(e, "setting times of", file))
+}
+
+/// access and modification time of the reference file
+fn reference_times(file: &str, dereference: bool) -> Result<(SystemTime, SystemTime)> {
+    let metadata = if dereference {
+        fs::metadata(file)
     } else {
-        SystemTime::now()
-    };
+        fs::symlink_metadata(file)
+    }
+    .map_err(|e| context(e, "failed to get attributes of", file))?;
+    Ok((metadata.accessed()?, metadata.modified()?))
+}
+
+/// Parse a date given to `-d`. Accepts `YYYY-MM-DD` optionally followed
+/// by ` hh:mm[:ss]` or `Thh:mm[:ss]`, `@seconds` since the epoch, `now`,
+/// `today`, `yesterday`, `tomorrow` and relative dates like `2 days ago`
+/// or `-3 hours`, in the local time zone.
+fn parse_date(date: &str) -> Result<SystemTime> {
+    let date = date.trim();
+    let bad = || invalid(format!("invalid date format '{}'", date));
+    let now = Local::now();
+
+    if let Some(secs) = date.strip_prefix('@') {
+        let secs: i64 = secs.parse().map_err(|_| bad())?;
+        return Local.timestamp_opt(secs, 0).single().map(SystemTime::from).ok_or_else(bad);
+    }
+    match date {
+        "now" | "today" => return Ok(now.into()),
+        "yesterday" => return Ok((now - Duration::days(1)).into()),
+        "tomorrow" => return Ok((now + Duration::days(1)).into()),
+        _ => {}
+    }
 
-    for f in src {
-        if !Path::new(f).exists() && flag {
-            fs::File::create(f)?;
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(date, format) {
+            return local(naive).ok_or_else(bad);
         }
-        func(f.to_string(), SystemTimeSpec::from(time))?;
     }
-    Ok(())
+    if let Ok(day) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        return day.and_hms_opt(0, 0, 0).and_then(local).ok_or_else(bad);
+    }
+
+    relative_date(date, now).map(SystemTime::from).ok_or_else(bad)
 }
 
-fn get_reference_timestamp(refer: &str) -> Option<SystemTime> {
-    if Path::new(refer).exists() {
-        return Some(fs::metadata(refer).unwrap().modified().unwrap());
+/// `N unit [ago]` pairs, added up, where the unit is one of
+/// seconds, minutes, hours, days, weeks or fortnights
+fn relative_date(date: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let words: Vec<&str> = date.split_whitespace().collect();
+    let (words, sign) = match words.split_last() {
+        Some((&"ago", rest)) => (rest, -1),
+        _ => (&words[..], 1),
+    };
+    if words.is_empty() || words.len() % 2 != 0 {
+        return None;
     }
-    None
+
+    words.chunks(2).try_fold(now, |res, pair| {
+        let n: i64 = pair[0].trim_start_matches('+').parse().ok()?;
+        let unit = pair[1].trim_end_matches('s');
+        let delta = match unit {
+            "sec" | "second" => Duration::seconds(n),
+            "min" | "minute" => Duration::minutes(n),
+            "hour" => Duration::hours(n),
+            "day" => Duration::days(n),
+            "week" => Duration::weeks(n),
+            "fortnight" => Duration::weeks(2 * n),
+            _ => return None,
+        };
+        Some(res + delta * sign)
+    })
+}
+
+/// Parse the `[[CC]YY]MMDDhhmm[.ss]` time stamp given to `-t`.
+/// Two digit years 69-99 are in the 20th century, 00-68 in the 21st.
+fn parse_stamp(stamp: &str) -> Result<SystemTime> {
+    let bad = || invalid(format!("invalid date format '{}'", stamp));
+    let (main, secs) = match stamp.split_once('.') {
+        Some((m, s)) if s.len() == 2 => (m, s),
+        Some(_) => return Err(bad()),
+        None => (stamp, "00"),
+    };
+    if !main.chars().chain(secs.chars()).all(|c| c.is_ascii_digit()) {
+        return Err(bad());
+    }
+
+    let year = match main.len() {
+        8 => Local::now().year().to_string(),
+        10 => match main[..2].parse::<u32>().map_err(|_| bad())? {
+            yy if yy >= 69 => format!("19{:02}", yy),
+            yy => format!("20{:02}", yy),
+        },
+        12 => main[..4].to_string(),
+        _ => return Err(bad()),
+    };
+    let rest = &main[main.len() - 8..];
+    let full = format!("{}{}{}", year, rest, secs);
+    let naive = NaiveDateTime::parse_from_str(&full, "%Y%m%d%H%M%S").map_err(|_| bad())?;
+    local(naive).ok_or_else(bad)
+}
+
+/// the time in the local time zone, `None` when it doesn't exist there
+fn local(naive: NaiveDateTime) -> Option<SystemTime> {
+    Local.from_local_datetime(&naive).earliest().map(SystemTime::from)
+}
+
+fn same(t: SystemTime) -> (SystemTime, SystemTime) {
+    (t, t)
+}
+
+fn invalid(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, msg)
+}
+
+/// add the action and the file name to the error message
+fn context(e: Error, action: &str, file: &str) -> Error {
+    Error::new(e.kind(), format!("{} '{}': {}", action, file, e))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::customs::{args, captured};
+    use std::path::Path;
     use core::time;
     use std::thread::sleep;
 
+    fn run(line: &str) -> ExitStatus {
+        captured(|io| touch(&args(line), io)).0
+    }
+
     #[test]
     fn test_create_file() {
         let filename = "test000.txt";
-        let mut token = Tokenizer::new("touch test000.txt");
-        let _ = touch(&mut token);
+        run("touch test000.txt");
 
         assert!(Path::new(filename).exists());
 
@@ -122,22 +400,50 @@ mod tests {
 
     #[test]
     fn test_parser() {
-        let mut line = Tokenizer::new("trulala -a nu 'patom paidzom'");
+        let line = args("trulala -a nu 'patom paidzom'");
         let expected: Vec<String> = vec![
-            "-a".to_string(), "nu".to_string(), "patom paidzom".to_string()
+            "nu".to_string(), "patom paidzom".to_string()
         ];
-        assert_eq!(expected, parse_command(&mut line).unwrap());
+        let (settings, files) = parse_options(&line[1..]).unwrap();
+        assert_eq!(expected, files);
+        assert!(settings.access && !settings.modify);
+    }
 
+    #[test]
+    fn test_parser_operands_and_groups() {
+        let line = args("touch '#notes' -cm @x -- -a - --no-cr");
+        let (settings, files) = parse_options(&line[1..]).unwrap();
+        assert_eq!(vec!["#notes", "@x", "-a", "-", "--no-cr"], files);
+        assert!(!settings.create && settings.modify && !settings.access);
+
+        let line = args("touch -ct200001010000 f --no-cr --tim=atime");
+        let (settings, files) = parse_options(&line[1..]).unwrap();
+        assert_eq!(vec!["f"], files);
+        assert!(!settings.create && settings.access && !settings.modify);
+        assert!(settings.times.is_some());
+
+        assert!(parse_options(&args("--no f")).is_err());
+        assert!(parse_options(&args("-d")).is_err());
+        assert!(parse_options(&args("--no-create=x f")).is_err());
+        assert!(parse_options(&args("-x f")).is_err());
+    }
 
+    #[test]
+    fn test_error_per_file() {
+        let (status, _, err) = captured(|io| {
+            touch(&args("touch no_such_dir_x/f test007.txt"), io)
+        });
+        assert_eq!(1, status);
+        assert!(err.starts_with("touch: cannot touch 'no_such_dir_x/f': "));
+        assert!(Path::new("test007.txt").exists());
+        fs::remove_file("test007.txt").unwrap();
     }
 
     #[test]
     fn test_create_with_space_in_name() {
         let filename1 = "test file";
         let filename2 = "second file";
-        let mut token = Tokenizer::new("touch 'test file' \"second file\"");
-
-        let _ = touch(&mut token);
+        run("touch 'test file' \"second file\"");
 
         assert!(Path::new(filename1).exists());
         assert!(Path::new(filename2).exists());
@@ -154,8 +460,7 @@ mod tests {
     #[test]
     fn test_no_file_creation() {
         let filename = "test001.txt";
-        let mut token = Tokenizer::new("touch -c test001.txt");
-        let _ = touch(&mut token);
+        run("touch -c test001.txt");
         assert!(!Path::new(filename).exists());
     }
 
@@ -166,9 +471,7 @@ mod tests {
             "multi02".to_string(),
             "multi03".to_string()
         ];
-        let mut token = Tokenizer::new("touch multi01 multi02 multi03");
-        let res = touch(&mut token);
-        assert!(res.is_ok());
+        assert_eq!(0, run("touch multi01 multi02 multi03"));
 
         for f in files.iter() {
             assert!(Path::new(f).exists());
@@ -184,14 +487,13 @@ mod tests {
     #[test]
     fn test_updated_modification() {
         let filename = "test002.txt";
-        let mut token = Tokenizer::new("touch test002.txt");
-        let _ = touch(&mut token);
+        run("touch test002.txt");
         let mut metadata = fs::metadata(filename).unwrap();
         let init_time = metadata.modified().unwrap();
 
         sleep(time::Duration::from_secs(1));
 
-        let _ = set_time(&[filename.to_string()], &None, false, set_mtime);
+        run("touch -m test002.txt");
         metadata = fs::metadata(filename).unwrap();
         let modified_time = metadata.modified().unwrap();
 
@@ -206,14 +508,13 @@ mod tests {
     #[test]
     fn test_updated_access() {
         let filename = "test003.txt";
-        let mut token = Tokenizer::new("touch test003.txt");
-        let _ = touch(&mut token);
+        run("touch test003.txt");
         let mut metadata = fs::metadata(filename).unwrap();
         let init_time = metadata.accessed().unwrap();
 
         sleep(time::Duration::from_secs(1));
 
-        let _ = set_time(&[filename.to_string()], &None, false, set_atime);
+        run("touch -a test003.txt");
         metadata = fs::metadata(filename).unwrap();
         let modified_time = metadata.accessed().unwrap();
 
@@ -228,15 +529,13 @@ mod tests {
     #[test]
     fn test_set_reffile_time() {
         let filename = "test004.txt";
-        let mut del_token = Tokenizer::new("touch delme");
-        let _ = touch(&mut del_token);
+        run("touch delme");
 
         let metadata = fs::metadata("delme").unwrap();
 
         sleep(time::Duration::from_secs(1));
 
-        let mut token = Tokenizer::new("touch -r delme test004.txt");
-        let _ = touch(&mut token);
+        run("touch -r delme test004.txt");
         let result_metadata = fs::metadata(filename).unwrap();
 
         assert_eq!(
@@ -252,4 +551,65 @@ mod tests {
             eprintln!("Can't remove delme");
         }
     }
+
+    fn local_time(date: &str) -> SystemTime {
+        let naive = NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").unwrap();
+        local(naive).unwrap()
+    }
+
+    #[test]
+    fn test_parse_stamp() {
+        assert_eq!(local_time("2024-01-02 03:04:00"), parse_stamp("202401020304").unwrap());
+        assert_eq!(local_time("1999-12-31 23:59:30"), parse_stamp("9912312359.30").unwrap());
+        assert_eq!(local_time("2011-05-06 07:08:00"), parse_stamp("1105060708").unwrap());
+        assert!(parse_stamp("13010000").is_err());
+        assert!(parse_stamp("2401020304.5").is_err());
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(local_time("2024-01-01 12:00:00"), parse_date("2024-01-01 12:00").unwrap());
+        assert_eq!(local_time("2024-01-01 00:00:00"), parse_date("2024-01-01").unwrap());
+        assert_eq!(SystemTime::UNIX_EPOCH, parse_date("@0").unwrap());
+
+        let now = Local::now();
+        let ago = relative_date("1 day 2 hours ago", now).unwrap();
+        assert_eq!(now - Duration::hours(26), ago);
+        assert_eq!(now + Duration::weeks(2), relative_date("+1 fortnight", now).unwrap());
+        assert!(parse_date("next blue moon").is_err());
+    }
+
+    #[test]
+    fn test_date_and_reference_times() {
+        let filename = "test005.txt";
+        let reference = "test005_ref.txt";
+        assert_eq!(0, run("touch -d '2020-02-03 04:05:06' test005_ref.txt"));
+        assert_eq!(0, run("touch --time=access --date=2001-01-01 test005_ref.txt"));
+        assert_eq!(0, run("touch --reference=test005_ref.txt test005.txt"));
+
+        let metadata = fs::metadata(filename).unwrap();
+        assert_eq!(local_time("2001-01-01 00:00:00"), metadata.accessed().unwrap());
+        assert_eq!(local_time("2020-02-03 04:05:06"), metadata.modified().unwrap());
+
+        assert_eq!(1, run("touch -r test005_ref.txt -t 202001010000 test005.txt"));
+        fs::remove_file(filename).unwrap();
+        fs::remove_file(reference).unwrap();
+    }
+
+    #[test]
+    fn test_symlink_itself() {
+        let target = "test006_target.txt";
+        let link = "test006_link";
+        assert_eq!(0, run("touch -t 200001010000 test006_target.txt"));
+        std::os::unix::fs::symlink(target, link).unwrap();
+
+        assert_eq!(0, run("touch -h -m -t 201001010000 test006_link"));
+        assert_eq!(local_time("2000-01-01 00:00:00"), fs::metadata(target).unwrap().modified().unwrap());
+        assert_eq!(local_time("2010-01-01 00:00:00"), fs::symlink_metadata(link).unwrap().modified().unwrap());
+
+        assert_eq!(1, run("touch --no-dereference test006_missing"));
+        assert!(!Path::new("test006_missing").exists());
+        fs::remove_file(link).unwrap();
+        fs::remove_file(target).unwrap();
+    }
 }
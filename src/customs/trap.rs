@@ -0,0 +1,119 @@
+use crate::customs::{Builtin, Context, ExitStatus, Io};
+use crate::signals;
+use crate::tokenizer;
+
+/// Implementation of the `trap` command. `trap command SIG ...` runs the
+/// command when one of the signals arrives, `trap - SIG` restores the
+/// default and `trap '' SIG` ignores the signal. Besides real signals
+/// `EXIT`, `ERR`, `DEBUG` and `RETURN` can be trapped.
+pub struct Trap;
+
+impl Builtin for Trap {
+    fn name(&self) -> &'static str {
+        "trap"
+    }
+
+    fn help(&self) -> &'static str {
+        "Run a command when the shell receives a signal. The pseudo-signals \
+         EXIT, ERR, DEBUG and RETURN trap the shell exiting, a command failing, \
+         every command and the end of a sourced script. `-p` lists the traps."
+    }
+
+    fn usage(&self) -> &'static str {
+        "trap [-p] [[command] signal ...]"
+    }
+
+    fn run(&self, _: &mut Context, args: &[String], io: &mut Io) -> ExitStatus {
+        let mut args = &args[1..];
+        if args.first().map(|a| a == "-p").unwrap_or(false) {
+            return print_traps(&args[1..], io);
+        }
+        if args.first().map(|a| a == "--").unwrap_or(false) {
+            args = &args[1..];
+        }
+        if args.is_empty() {
+            return print_traps(&[], io);
+        }
+        if let Some(a) = args.first().filter(|a| a.starts_with('-') && *a != "-") {
+            let _ = writeln!(io.stderr, "trap: {}: invalid option", a);
+            let _ = writeln!(io.stderr, "trap: usage: {}", self.usage());
+            return 2;
+        }
+
+        // a lone signal or a list starting with a number is reset
+        let (command, specs) = if args.len() == 1 || args[0].parse::<u32>().is_ok() {
+            (None, args)
+        } else if args[0] == "-" {
+            (None, &args[1..])
+        } else {
+            (Some(args[0].as_str()), &args[1..])
+        };
+
+        let mut status = 0;
+        for spec in specs {
+            let result = match signals::parse(spec) {
+                Some(id) => signals::set_trap(id, command),
+                None => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "invalid signal specification",
+                )),
+            };
+            if let Err(e) = result {
+                let _ = writeln!(io.stderr, "trap: {}: {}", spec, e);
+                status = 1;
+            }
+        }
+        status
+    }
+}
+
+/// print the traps in a form which can be read back by the shell
+fn print_traps(specs: &[String], io: &mut Io) -> ExitStatus {
+    let mut status = 0;
+    let mut wanted = vec![];
+    for spec in specs {
+        match signals::parse(spec) {
+            Some(id) => wanted.push(id),
+            None => {
+                let _ = writeln!(io.stderr, "trap: {}: invalid signal specification", spec);
+                status = 1;
+            }
+        }
+    }
+    for (id, name, command) in signals::traps() {
+        if wanted.is_empty() || wanted.contains(&id) {
+            let _ = writeln!(io.stdout, "trap -- {} {}", tokenizer::quote(&command), name);
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::customs::run_captured;
+
+    #[test]
+    fn test_set_print_and_reset() {
+        assert_eq!(0, run_captured(&Trap, "trap 'echo it'\\''s over' exit sigusr2 RETURN").0);
+        assert_eq!(0, run_captured(&Trap, "trap '' 31").0);
+        let (_, out, _) = run_captured(&Trap, "trap -p EXIT USR2 SIGSYS");
+        assert_eq!("trap -- 'echo it'\\''s over' EXIT\n\
+                    trap -- 'echo it'\\''s over' SIGUSR2\n\
+                    trap -- '' SIGSYS\n", out);
+
+        run_captured(&Trap, "trap - EXIT SIGUSR2");
+        run_captured(&Trap, "trap RETURN");
+        run_captured(&Trap, "trap 31");
+        assert_eq!(None, signals::get_trap(signals::EXIT));
+        assert_eq!(None, signals::get_trap(signals::RETURN));
+        assert_eq!(None, signals::get_trap(31));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let (status, _, err) = run_captured(&Trap, "trap 'echo x' NOPE KILL");
+        assert_eq!(1, status);
+        assert_eq!("trap: NOPE: invalid signal specification\ntrap: KILL: cannot be trapped\n", err);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::customs::{abbr, history};
+use crate::sys;
+use std::io::{self, Write};
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use termion::{clear, cursor};
+
+/// What the editor does after a key was pressed
+#[derive(Debug, PartialEq)]
+enum Action {
+    Continue,
+    Submit(String),
+    Cancel,
+    Eof,
+}
+
+/// Line being edited with the position of the cursor in it
+struct Editor {
+    buffer: Vec<char>,
+    cursor: usize,
+    history: Vec<String>,
+    /// position in the history, its length while editing a new line
+    index: usize,
+    /// the new line, kept while browsing the history
+    draft: Vec<char>,
+}
+
+impl Editor {
+    fn new(history: Vec<String>) -> Self {
+        Editor {
+            buffer: vec![],
+            cursor: 0,
+            index: history.len(),
+            history,
+            draft: vec![],
+        }
+    }
+
+    fn line(&self) -> String {
+        self.buffer.iter().collect()
+    }
+
+    fn key(&mut self, key: Key) -> Action {
+        match key {
+            Key::Char('\n') | Key::Char('\r') => {
+                self.expand_abbreviation();
+                return Action::Submit(self.line());
+            }
+            Key::Char(' ') => {
+                self.expand_abbreviation();
+                self.insert(' ');
+            }
+            Key::Char('\t') => {}
+            Key::Char(c) => self.insert(c),
+            Key::Backspace | Key::Ctrl('h') if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.buffer.remove(self.cursor);
+            }
+            Key::Ctrl('d') if self.buffer.is_empty() => return Action::Eof,
+            Key::Delete | Key::Ctrl('d') if self.cursor < self.buffer.len() => {
+                self.buffer.remove(self.cursor);
+            }
+            Key::Left | Key::Ctrl('b') => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right | Key::Ctrl('f') => self.cursor = (self.cursor + 1).min(self.buffer.len()),
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = self.buffer.len(),
+            Key::Ctrl('u') => {
+                self.buffer.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::Ctrl('k') => self.buffer.truncate(self.cursor),
+            Key::Up | Key::Ctrl('p') if self.index > 0 => self.browse(self.index - 1),
+            Key::Down | Key::Ctrl('n') if self.index < self.history.len() => {
+                self.browse(self.index + 1)
+            }
+            Key::Ctrl('c') => return Action::Cancel,
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    /// replace the history line or the draft shown by another one
+    fn browse(&mut self, index: usize) {
+        if self.index == self.history.len() {
+            self.draft = self.buffer.clone();
+        }
+        self.index = index;
+        self.buffer = match self.history.get(index) {
+            Some(line) => line.chars().collect(),
+            None => self.draft.clone(),
+        };
+        self.cursor = self.buffer.len();
+    }
+
+    /// expand an abbreviation which ends at the cursor
+    fn expand_abbreviation(&mut self) {
+        let before: String = self.buffer[..self.cursor].iter().collect();
+        if let Some(expanded) = abbr::expand_before(&before) {
+            let expanded: Vec<char> = expanded.chars().collect();
+            let len = expanded.len();
+            self.buffer.splice(..self.cursor, expanded);
+            self.cursor = len;
+        }
+    }
+
+    /// redraw the last line of the prompt followed by the buffer
+    fn render(&self, out: &mut dyn Write, prompt: &str) -> io::Result<()> {
+        write!(out, "\r{}{}{}", prompt, self.line(), clear::AfterCursor)?;
+        let back = self.buffer.len() - self.cursor;
+        if back > 0 {
+            write!(out, "{}", cursor::Left(back as u16))?;
+        }
+        out.flush()
+    }
+}
+
+/// Read a line typed by the user after the prompt, which was already
+/// printed. On a terminal the line can be edited, arrows move through it
+/// and the history and abbreviations are expanded as they're typed.
+/// Returns `None` at the end of the input.
+pub fn read_line(prompt: &str) -> io::Result<Option<String>> {
+    let stdin = io::stdin();
+    if !termion::is_tty(&stdin) {
+        // the rest of the input is left to the commands, e.g. to `read`
+        let line = sys::read_line(0)?;
+        return Ok(line.map(|l| l.trim_end_matches('\n').to_string()));
+    }
+
+    // only the last line of the prompt is redrawn
+    let last_line = prompt.rsplit('\n').next().unwrap_or("");
+    let mut stdout = io::stdout().into_raw_mode()?;
+    let mut editor = Editor::new(history::lines());
+    for key in stdin.keys() {
+        match editor.key(key?) {
+            Action::Continue => editor.render(&mut stdout, last_line)?,
+            Action::Submit(line) => {
+                editor.render(&mut stdout, last_line)?;
+                write!(stdout, "\r\n")?;
+                return Ok(Some(line));
+            }
+            Action::Cancel => {
+                write!(stdout, "^C\r\n{}", prompt.replace('\n', "\r\n"))?;
+                stdout.flush()?;
+                editor = Editor::new(history::lines());
+            }
+            Action::Eof => {
+                write!(stdout, "\r\n")?;
+                return Ok(None);
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(editor: &mut Editor, text: &str) {
+        for c in text.chars() {
+            editor.key(Key::Char(c));
+        }
+    }
+
+    #[test]
+    fn test_cursor_movement() {
+        let mut editor = Editor::new(vec![]);
+        typed(&mut editor, "ech");
+        editor.key(Key::Home);
+        typed(&mut editor, "x");
+        editor.key(Key::End);
+        typed(&mut editor, "o");
+        editor.key(Key::Left);
+        editor.key(Key::Backspace);
+        editor.key(Key::Ctrl('a'));
+        editor.key(Key::Delete);
+        assert_eq!(Action::Submit("eco".to_string()), editor.key(Key::Char('\n')));
+    }
+
+    #[test]
+    fn test_history() {
+        let mut editor = Editor::new(vec!["ls".to_string(), "pwd".to_string()]);
+        typed(&mut editor, "dra");
+        editor.key(Key::Up);
+        editor.key(Key::Up);
+        assert_eq!("ls", editor.line());
+        editor.key(Key::Up);
+        editor.key(Key::Down);
+        assert_eq!("pwd", editor.line());
+        editor.key(Key::Down);
+        assert_eq!("dra", editor.line());
+        assert_eq!(3, editor.cursor);
+    }
+
+    #[test]
+    fn test_eof_and_cancel() {
+        let mut editor = Editor::new(vec![]);
+        typed(&mut editor, "x");
+        assert_eq!(Action::Continue, editor.key(Key::Ctrl('d')));
+        editor.key(Key::Left);
+        editor.key(Key::Ctrl('d'));
+        assert_eq!(Action::Eof, editor.key(Key::Ctrl('d')));
+        assert_eq!(Action::Cancel, editor.key(Key::Ctrl('c')));
+    }
+}
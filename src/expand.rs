@@ -0,0 +1,472 @@
+use crate::customs::dirstack;
+use crate::{arith, options, shellname::ShellName, sys, tokenizer, vars};
+use users::os::unix::UserExt;
+use crate::tokenizer::Tokenizer;
+use nix::fcntl::OFlag;
+use nix::unistd::{close, dup2, pipe2};
+use std::cell::Cell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read};
+use std::os::unix::io::FromRawFd;
+
+thread_local! {
+    /// Exit status of the last command substitution since it was taken
+    static SUBSTITUTION: Cell<Option<i32>> = const { Cell::new(None) };
+}
+
+/// Status of the last command substitution expanded since the previous
+/// call, the status of a command made only of assignments
+pub fn take_substitution_status() -> Option<i32> {
+    SUBSTITUTION.with(|s| s.take())
+}
+
+/// Expand parameters (`$NAME`, `${NAME}`, `$?`, `$-`), arithmetic (`$(( ))`) and
+/// command substitutions (`$( )` and backticks) in a command line.
+/// Text inside single quotes is left as is.
+/// Values inserted outside of double quotes are split into fields on
+/// `IFS`, every field is quoted so it is read back as a separate word.
+pub fn expand_line(shell_name: &mut ShellName, line: &str) -> io::Result<String> {
+    let mut words = vec![];
+    // assignments before the command name are never split into fields
+    let mut assignments = true;
+
+    for word in tokenizer::raw_words(line) {
+        if word == "|" {
+            assignments = true;
+            words.push(word);
+        } else if word.starts_with("((") {
+            // arithmetic command is evaluated as a whole
+            words.push(expand_word(shell_name, &word)?);
+        } else if tokenizer::is_group(&word) {
+            // commands of a group are expanded when each of them runs
+            assignments = false;
+            words.push(word);
+        } else {
+            assignments = assignments && vars::split_assignment(&word).is_some();
+            words.push(expand_raw(shell_name, &word, !assignments)?);
+        }
+    }
+    Ok(words.join(" "))
+}
+
+/// Expand a single word as it was typed, keeping its quotation
+/// marks. With `split` set unquoted values are split into fields.
+fn expand_raw(shell_name: &mut ShellName, word: &str, split: bool) -> io::Result<String> {
+    let (mut res, mut i) = match tilde_prefix(word) {
+        Some((dir, len)) => (tokenizer::quote(&dir), len),
+        None => (String::new(), 0),
+    };
+    let mut in_double = false;
+
+    while i < word.len() {
+        let c = word[i..].chars().next().unwrap();
+        match c {
+            '\'' if !in_double => {
+                let end = word[i + 1..].find('\'').map_or(word.len(), |e| i + 1 + e + 1);
+                res.push_str(&word[i..end]);
+                i = end;
+                continue;
+            }
+            '"' => in_double = !in_double,
+            '\\' => {
+                // keep the escape and the escaped character for the tokenizer
+                let n = word[i + 1..].chars().next().map_or(0, |n| n.len_utf8());
+                res.push_str(&word[i..i + 1 + n]);
+                i += 1 + n;
+                continue;
+            }
+            '$' | '`' => {
+                if let Some((value, len)) = expansion(shell_name, &word[i..])? {
+                    if in_double {
+                        res.push_str(&escape_double(&value));
+                    } else if split {
+                        res.push_str(&split_fields(&value));
+                    } else {
+                        res.push_str(&tokenizer::quote(&value));
+                    }
+                    i += len;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        res.push(c);
+        i += c.len_utf8();
+    }
+    Ok(res)
+}
+
+/// Expand an unquoted `~` at the start of the word up to the first slash:
+/// `~` is the home directory, `~user` the home directory of the user,
+/// `~+` and `~-` are `PWD` and `OLDPWD`, `~N` is an entry of the directory
+/// stack. Returns the directory and the
+/// length of the prefix it replaces.
+fn tilde_prefix(word: &str) -> Option<(String, usize)> {
+    let rest = word.strip_prefix('~')?;
+    let end = rest.find('/').unwrap_or(rest.len());
+    let name = &rest[..end];
+    if name.contains(['\'', '"', '\\', '$', '`']) {
+        return None;
+    }
+
+    let dir = match name {
+        "" => vars::get("HOME")
+            .or_else(|| dirs::home_dir().map(|h| h.to_string_lossy().to_string())),
+        "+" => vars::get("PWD"),
+        "-" => vars::get("OLDPWD"),
+        n if n.strip_prefix(['+', '-']).unwrap_or(n).parse::<usize>().is_ok() => {
+            dirstack::entry(n)
+        }
+        user => users::get_user_by_name(user)
+            .map(|u| u.home_dir().to_string_lossy().to_string()),
+    }?;
+    Some((dir, 1 + end))
+}
+
+/// Expand the text without quote removal or field splitting,
+/// used for the contents of arithmetic expressions
+pub fn expand_word(shell_name: &mut ShellName, text: &str) -> io::Result<String> {
+    let mut res = String::new();
+    let mut i = 0;
+
+    while i < text.len() {
+        let c = text[i..].chars().next().unwrap();
+        if c == '$' || c == '`' {
+            if let Some((value, len)) = expansion(shell_name, &text[i..])? {
+                res.push_str(&value);
+                i += len;
+                continue;
+            }
+        }
+        res.push(c);
+        i += c.len_utf8();
+    }
+    Ok(res)
+}
+
+/// Expands a single `$` or backtick construct at the start of `text`.
+/// Returns the value and how many bytes were consumed,
+/// or `None` if it isn't a valid expansion.
+fn expansion(shell_name: &mut ShellName, text: &str) -> io::Result<Option<(String, usize)>> {
+    if text.starts_with('`') {
+        let end = tokenizer::closing_backtick(text, 1).ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "unexpected EOF while looking for matching ``'")
+        })?;
+        let cmd = unescape_backticks(&text[1..end]);
+        return Ok(Some((command_substitution(shell_name, &cmd)?, end + 1)));
+    }
+
+    let rest = &text[1..];
+
+    if rest.starts_with('(') {
+        let end = tokenizer::closing_paren(text, 1).ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "unexpected EOF while looking for matching `)'")
+        })?;
+        // `$((` is arithmetic only when it's closed by `))`
+        if let Some(expr) = text[2..end].strip_prefix('(').and_then(|e| e.strip_suffix(')')) {
+            let value = arith::evaluate(&expand_word(shell_name, expr)?)?;
+            return Ok(Some((value.to_string(), end + 1)));
+        }
+        return Ok(Some((command_substitution(shell_name, &text[2..end])?, end + 1)));
+    }
+
+    if let Some(braced) = rest.strip_prefix('{') {
+        let end = braced.find('}').ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "bad substitution")
+        })?;
+        let name = &braced[..end];
+        if let Some((array, index)) = name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+            if vars::is_valid_name(array) {
+                return Ok(Some((element(array, index)?, end + 3)));
+            }
+        }
+        if !vars::is_valid_name(name) && name != "?" && name != "-" {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("${{{}}}: bad substitution", name),
+            ));
+        }
+        return Ok(Some((variable(name)?, end + 3)));
+    }
+
+    if rest.starts_with('?') {
+        return Ok(Some((vars::status().to_string(), 2)));
+    }
+    if rest.starts_with('-') {
+        return Ok(Some((options::flags(), 2)));
+    }
+
+    let len = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len());
+    let name = &rest[..len];
+    if vars::is_valid_name(name) {
+        return Ok(Some((variable(name)?, len + 1)));
+    }
+    Ok(None)
+}
+
+/// Value of a parameter, an unset one is empty unless `nounset` is on
+fn variable(name: &str) -> io::Result<String> {
+    if name == "-" {
+        return Ok(options::flags());
+    }
+    // the name of an array stands for its first element
+    match vars::get(name).or_else(|| vars::get(&format!("{}[0]", name))) {
+        Some(value) => Ok(value),
+        None if options::is_set("nounset") => {
+            Err(io::Error::new(ErrorKind::InvalidInput, Unbound(name.to_string())))
+        }
+        None => Ok(String::new()),
+    }
+}
+
+/// Element `${name[N]}` of an array, `@` or `*` give all
+/// of them separated by spaces
+fn element(array: &str, index: &str) -> io::Result<String> {
+    match index {
+        "@" | "*" => Ok(vars::array(array).join(" ")),
+        _ => match index.parse::<usize>() {
+            Ok(i) => variable(&format!("{}[{}]", array, i)),
+            Err(_) => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{}[{}]: bad array subscript", array, index),
+            )),
+        },
+    }
+}
+
+/// Error of expanding an unset variable with `nounset` on,
+/// a shell which isn't interactive exits on it
+#[derive(Debug)]
+struct Unbound(String);
+
+impl fmt::Display for Unbound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: unbound variable", self.0)
+    }
+}
+
+impl std::error::Error for Unbound {}
+
+/// checks if the error is about an unset variable expanded with `nounset` on
+pub fn is_unbound(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<Unbound>())
+}
+
+/// Run the command in a subshell and capture its standard output.
+/// Trailing newlines are removed from the captured output.
+fn command_substitution(shell_name: &mut ShellName, cmd: &str) -> io::Result<String> {
+    let (reader, writer) = pipe2(OFlag::O_CLOEXEC).map_err(sys::io_err)?;
+
+    let child = sys::fork_child(|| {
+        if dup2(writer, 1).is_err() {
+            return 1;
+        }
+        crate::run_line(shell_name, &mut Tokenizer::new(cmd))
+    });
+    let _ = close(writer);
+
+    // reading until every copy of the pipe's write end is closed
+    let mut output = vec![];
+    let mut pipe = unsafe { File::from_raw_fd(reader) };
+    let read = pipe.read_to_end(&mut output);
+
+    let status = sys::wait_for(child?)?;
+    vars::set_status(status);
+    SUBSTITUTION.with(|s| s.set(Some(status)));
+    read?;
+
+    let mut output = String::from_utf8_lossy(&output).into_owned();
+    while output.ends_with('\n') {
+        output.pop();
+    }
+    Ok(output)
+}
+
+/// inside backticks a backslash only escapes `$`, `` ` `` and `\`
+fn unescape_backticks(cmd: &str) -> String {
+    let mut res = String::new();
+    let mut chars = cmd.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && matches!(chars.peek(), Some('$') | Some('`') | Some('\\')) {
+            continue;
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// escape characters which are special inside double quotes
+fn escape_double(value: &str) -> String {
+    let mut res = String::new();
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// split an unquoted value into fields on the characters of `IFS`
+pub fn split_fields(value: &str) -> String {
+    let ifs = vars::get("IFS").unwrap_or_else(|| " \t\n".to_string());
+    let mut res = String::new();
+
+    if value.starts_with(|c| ifs.contains(c)) {
+        res.push(' ');
+    }
+    let fields: Vec<_> = value
+        .split(|c| ifs.contains(c))
+        .filter(|f| !f.is_empty())
+        .map(tokenizer::quote)
+        .collect();
+    res.push_str(&fields.join(" "));
+    if value.ends_with(|c| ifs.contains(c)) {
+        res.push(' ');
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(line: &str) -> io::Result<String> {
+        let mut sh = ShellName::new("test");
+        expand_line(&mut sh, line)
+    }
+
+    #[test]
+    fn test_tilde_expansion() {
+        let home = vars::get("HOME").unwrap();
+        let root = users::get_user_by_uid(0).unwrap();
+        let line = format!("echo ~/sub '~'/sub a~ ~no_such_user_x ~{}", root.name().to_string_lossy());
+        let words: Vec<_> = Tokenizer::new(&expand(&line).unwrap()).collect();
+        assert_eq!(
+            vec![
+                "echo".to_string(),
+                format!("{}/sub", home),
+                "~/sub".to_string(),
+                "a~".to_string(),
+                "~no_such_user_x".to_string(),
+                root.home_dir().to_string_lossy().to_string(),
+            ],
+            words
+        );
+    }
+
+    #[test]
+    fn test_parameter_expansion() {
+        vars::set("expand_x", "hello world");
+        let line = expand("echo $expand_x \"$expand_x\" '$expand_x' ${expand_x}!").unwrap();
+        let words: Vec<_> = Tokenizer::new(&line).collect();
+        assert_eq!(
+            vec!["echo", "hello", "world", "hello world", "$expand_x", "hello", "world!"],
+            words
+        );
+        vars::unset("expand_x");
+    }
+
+    #[test]
+    fn test_array_elements() {
+        vars::set_array("expand_arr", &["a b".to_string(), "c".to_string()]);
+        let words = |line: &str| Tokenizer::new(&expand(line).unwrap()).collect::<Vec<_>>();
+        assert_eq!(vec!["echo", "c", "a", "b"], words("echo ${expand_arr[1]} $expand_arr"));
+        assert_eq!(vec!["echo", "a b c"], words("echo \"${expand_arr[@]}\" ${expand_arr[2]}"));
+        assert!(expand("echo ${expand_arr[x]}").is_err());
+        vars::set_array("expand_arr", &[]);
+    }
+
+    #[test]
+    fn test_arithmetic_expansion() {
+        vars::set("expand_n", "4");
+        let line = expand("echo $(( expand_n * (2 + $expand_n) )) $((1<<2))").unwrap();
+        assert_eq!("echo '24' '4'", line);
+        vars::unset("expand_n");
+    }
+
+    #[test]
+    fn test_arithmetic_command() {
+        vars::set("expand_c", "1 2");
+        let line = expand("(( $expand_c ))").unwrap();
+        assert_eq!("(( 1 2 ))", line);
+        vars::unset("expand_c");
+    }
+
+    #[test]
+    fn test_assignment_is_not_split() {
+        let line = expand("subst_a=$(printf 'a  b') echo $(echo c d)").unwrap();
+        let words: Vec<_> = Tokenizer::new(&line).collect();
+        assert_eq!(vec!["subst_a=a  b", "echo", "c", "d"], words);
+    }
+
+    #[test]
+    fn test_group_is_not_expanded() {
+        let line = expand("( echo $expand_g ) | { echo $expand_g; } > $expand_g").unwrap();
+        assert_eq!("( echo $expand_g ) | { echo $expand_g; } > ", line);
+    }
+
+    #[test]
+    fn test_unset_is_empty() {
+        let words: Vec<_> = Tokenizer::new(&expand("a $expand_none b").unwrap()).collect();
+        assert_eq!(vec!["a", "b"], words);
+    }
+
+    #[test]
+    fn test_unbound_error() {
+        // other tests expand unset variables, so `nounset` isn't turned on
+        let e = io::Error::new(ErrorKind::InvalidInput, Unbound("expand_unbound".to_string()));
+        assert!(is_unbound(&e));
+        assert_eq!("expand_unbound: unbound variable", e.to_string());
+        assert!(!is_unbound(&io::Error::new(ErrorKind::InvalidInput, "bad substitution")));
+    }
+
+    #[test]
+    fn test_option_flags() {
+        // other tests change the options, so only the letters are checked
+        let line = expand("echo \"$-\" \"${-}\"").unwrap();
+        let words: Vec<_> = Tokenizer::new(&line).collect();
+        assert_eq!(3, words.len());
+        assert!(words[1..].iter().all(|w| w.chars().all(|c| "Cefuxi".contains(c))));
+    }
+
+    #[test]
+    fn test_command_substitution() {
+        let line = expand("echo $(echo hello   world) \"$(printf 'a\\n\\n')\"").unwrap();
+        let words: Vec<_> = Tokenizer::new(&line).collect();
+        assert_eq!(vec!["echo", "hello", "world", "a"], words);
+    }
+
+    #[test]
+    fn test_substitution_status() {
+        take_substitution_status();
+        expand("expand_s=$(false) expand_t=$(echo)").unwrap();
+        assert_eq!(Some(0), take_substitution_status());
+        expand("expand_s=$(exit 3)").unwrap();
+        assert_eq!(Some(3), take_substitution_status());
+        expand("expand_s=x").unwrap();
+        assert_eq!(None, take_substitution_status());
+    }
+
+    #[test]
+    fn test_nested_substitution() {
+        let line = expand("$(echo $(echo nested)) `echo \\`echo tick\\``").unwrap();
+        let words: Vec<_> = Tokenizer::new(&line).collect();
+        assert_eq!(vec!["nested", "tick"], words);
+    }
+
+    #[test]
+    fn test_substitution_with_builtins() {
+        let line = expand("$(let 'subst_v = 7' && echo $((subst_v * 2)))").unwrap();
+        assert_eq!("'14'", line);
+        assert_eq!(None, vars::get("subst_v"));
+    }
+
+    #[test]
+    fn test_division_by_zero_error() {
+        assert!(expand("echo $((1 / 0))").is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::tokenizer::{self, unquote};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, process};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Read the bodies of here-documents (`<<DELIM` and `<<-DELIM`) used in the
+/// line, `next_line` supplies the lines which follow the command.
+/// Each here-document is replaced by a `<<` operator followed by its body as
+/// a single word. When the delimiter is quoted the body is single quoted so no
+/// expansion happens, otherwise it is double quoted like bash expands it.
+pub fn collect_heredocs(
+    line: &str,
+    next_line: &mut dyn FnMut() -> Option<String>,
+) -> io::Result<String> {
+    let words = tokenizer::raw_words(line);
+    if !words.iter().any(|w| is_heredoc(w)) {
+        return Ok(line.to_string());
+    }
+
+    let mut res = vec![];
+    let mut iter = words.into_iter();
+    while let Some(word) = iter.next() {
+        if !is_heredoc(&word) {
+            res.push(word);
+            continue;
+        }
+
+        let strip_tabs = word.starts_with("<<-");
+        let op_len = if strip_tabs { 3 } else { 2 };
+        let delim = if word.len() > op_len {
+            word[op_len..].to_string()
+        } else {
+            iter.next().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "syntax error near `newline'")
+            })?
+        };
+
+        let quoted = delim.contains(['\'', '"', '\\']);
+        let delim = unquote(&delim);
+        let body = read_body(&delim, strip_tabs, next_line);
+
+        res.push("<<".to_string());
+        res.push(if quoted {
+            tokenizer::quote(&body)
+        } else {
+            double_quote(&body)
+        });
+    }
+    Ok(res.join(" "))
+}
+
+/// checks if a raw word is a here-document operator
+fn is_heredoc(word: &str) -> bool {
+    word.starts_with("<<") && !word.starts_with("<<<")
+}
+
+fn read_body(
+    delim: &str,
+    strip_tabs: bool,
+    next_line: &mut dyn FnMut() -> Option<String>,
+) -> String {
+    let mut body = String::new();
+    loop {
+        let mut line = match next_line() {
+            Some(l) => l,
+            None => {
+                eprintln!("warning: here-document delimited by end-of-file (wanted `{}')", delim);
+                break;
+            }
+        };
+        if line.ends_with('\n') {
+            line.pop();
+        }
+        if strip_tabs {
+            line = line.trim_start_matches('\t').to_string();
+        }
+        if line == delim {
+            break;
+        }
+        body.push_str(&line);
+        body.push('\n');
+    }
+    body
+}
+
+/// Wrap the body in double quotation marks. A backslash in a here-document
+/// doesn't escape a double quote, so that backslash is escaped itself.
+fn double_quote(body: &str) -> String {
+    let mut res = String::from("\"");
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' if chars.peek() == Some(&'"') => res.push_str("\\\\"),
+            _ => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// Store the contents in an unlinked temporary file
+/// and return it opened for reading from the start.
+pub fn open_document(contents: &str) -> io::Result<File> {
+    let path = env::temp_dir().join(format!(
+        "cr4sh_heredoc_{}_{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let mut f = OpenOptions::new().write(true).create_new(true).open(&path)?;
+    f.write_all(contents.as_bytes())?;
+
+    let reader = File::open(&path);
+    fs::remove_file(&path)?;
+    reader
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::Tokenizer;
+    use std::io::Read;
+
+    fn lines(input: &[&str]) -> impl FnMut() -> Option<String> {
+        let mut v: Vec<String> = input.iter().map(|l| format!("{}\n", l)).collect();
+        v.reverse();
+        move || v.pop()
+    }
+
+    #[test]
+    fn test_heredoc_body() {
+        let mut next = lines(&["hello $USER", "  \"quoted\"", "EOF", "rest"]);
+        let line = collect_heredocs("cat <<EOF", &mut next).unwrap();
+        assert_eq!("cat << \"hello $USER\n  \\\"quoted\\\"\n\"", line);
+
+        let mut tok = Tokenizer::new(&line);
+        tok.next();
+        tok.next();
+        assert_eq!("hello $USER\n  \"quoted\"\n", tok.next().unwrap());
+        assert_eq!(Some("rest\n".to_string()), next());
+    }
+
+    #[test]
+    fn test_quoted_delimiter() {
+        let mut next = lines(&["$HOME `x`", "END"]);
+        let line = collect_heredocs("cat << 'END' > out", &mut next).unwrap();
+        assert_eq!("cat << '$HOME `x`\n' > out", line);
+    }
+
+    #[test]
+    fn test_strip_tabs() {
+        let mut next = lines(&["\t\tindented", "\tEOF"]);
+        let line = collect_heredocs("cat <<-EOF", &mut next).unwrap();
+        assert_eq!("cat << \"indented\n\"", line);
+    }
+
+    #[test]
+    fn test_multiple_and_herestring() {
+        let mut next = lines(&["one", "A", "two", "B"]);
+        let line = collect_heredocs("a <<A <<<word <<B", &mut next).unwrap();
+        assert_eq!("a << \"one\n\" <<<word << \"two\n\"", line);
+    }
+
+    #[test]
+    fn test_open_document() {
+        let mut f = open_document("some text\n").unwrap();
+        let mut s = String::new();
+        f.read_to_string(&mut s).unwrap();
+        assert_eq!("some text\n", s);
+    }
+}
@@ -1,259 +1,507 @@
+mod arith;
 mod customs;
+mod editor;
+mod expand;
+mod heredoc;
+mod options;
+mod redirect;
 mod shellname;
+mod signals;
+mod suggest;
+mod sys;
 mod tokenizer;
+mod vars;
 
 #[macro_use]
 extern crate lazy_static;
 
 use crate::{shellname::*, tokenizer::*};
-use crate::customs::{cd, touch};
-use signal_hook::{
-    consts::{SIGINT, SIGQUIT},
-    iterator,
-};
-use std::collections::HashSet;
-use std::{
-    env::current_dir,
-    fs::{File, OpenOptions},
-};
+use crate::customs::{alias, arithmetic, cd, exit, hash, history, Builtin, Context};
+use crate::redirect::{Redirection, Redirections};
+use nix::fcntl::OFlag;
+use nix::unistd::{close, dup2, pipe2, Pid};
+use std::fs::File;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
 use std::{
-    error::Error,
     io::{self, Write},
-    process, thread,
+    process,
 };
 
-lazy_static! {
-    /// Global HashSet that contains all the internally defined shell functions
-    static ref CUSTOM_FN: HashSet<&'static str> = {
-        vec!["cd", "source", "touch", "history", ">"]
-            .into_iter()
-            .collect()
-    };
-}
+/// Command run with the words of a command which isn't found, when it's defined.
+/// The shell has no functions, so it's defined as an alias.
+const NOT_FOUND_HANDLER: &str = "command_not_found_handle";
+
+/// Number of commands being run whose status is tested by `&&` or `||`.
+/// Their failures, and those of commands they run, don't trigger the
+/// `ERR` trap or `errexit`.
+static TESTED: AtomicUsize = AtomicUsize::new(0);
 
 fn main() {
-    if register_signal_handlers().is_err() {
-        println!("Signals are not handled properly");
+    if termion::is_tty(&io::stdin()) {
+        if signals::ignore_interactive().is_err() {
+            println!("Signals are not handled properly");
+        }
+        history::load();
     }
 
-    let cur = current_dir().unwrap();
-    let cur = cur.strip_prefix(dirs::home_dir().unwrap()).unwrap();
+    let cur = cd::init_pwd().unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        String::from(".")
+    });
 
     // create initial shell terminal display
-    let mut minishell = ShellName::new(cur.to_str().unwrap());
+    let mut minishell = ShellName::new("");
+    minishell.set_current_dir(&cur);
 
     loop {
         run_shell(&mut minishell);
     }
 }
 
-/// Register UNIX system signals
-fn register_signal_handlers() -> Result<(), Box<dyn Error>> {
-    let mut signals = iterator::Signals::new(&[SIGINT, SIGQUIT])?;
-
-    // signal execution is passed to the child process
-    thread::spawn(move || {
-        for sig in signals.forever() {
-            match sig {
-                SIGQUIT => process::exit(0),
-                SIGINT => assert_eq!(2, sig), // assert that the signal is sent
-                _ => continue,
-            }
-        }
-    });
-
-    Ok(())
-}
-
 /// Run the minishell to execute user supplied instructions
 fn run_shell(shell_name: &mut ShellName) {
     write_to_stdout(&shell_name.shell_name)
                 .expect("Unable to write to stdout");
 
-    let mut cmd_line = match get_user_commands() {
-        Ok(t) => t,
+    let mut cmd_line = match get_user_commands(&shell_name.shell_name) {
+        Ok(Some(t)) => t,
+        Ok(None) if options::is_set("ignoreeof") && signals::is_interactive() => {
+            eprintln!("Use \"exit\" to leave the shell.");
+            return;
+        }
+        Ok(None) => exit::leave(shell_name, vars::status()),
         Err(e) => {
             eprintln!("Error: {}", e);
             return;
         }
     };
 
-    for mut token in cmd_line.get_multiple_tokens("&&") {
-        if CUSTOM_FN.contains(&token.peek()[0..]) {
-            if let Err(e) = execute_custom_fn(shell_name, &mut token) {
-                eprint!("Error: {}", e);
-            }
-            continue;
-        } else if token.is_pipe() {
-            if let Err(e) = piped_cmd_execution(&mut token) {
-                eprintln!("Error: {}", e);
+    // an empty line just shows the prompt again
+    if cmd_line.remainder().trim().is_empty() {
+        return;
+    }
+    history::add(cmd_line.remainder());
+    run_line(shell_name, &mut cmd_line);
+}
+
+/// Execute a line of commands separated by `;`, `&&` and `||`.
+/// Returns the exit status of the last executed command.
+pub fn run_line(shell_name: &mut ShellName, cmd_line: &mut Tokenizer) -> i32 {
+    let mut run = true;
+    let line = alias::expand(cmd_line.remainder());
+    for (token, op) in Tokenizer::new(&line).command_list() {
+        if run {
+            signals::run_trap(shell_name, signals::DEBUG);
+            // a failure which only decides if `&&` or `||` runs isn't an error
+            let tested = op == "&&" || op == "||";
+            if tested {
+                TESTED.fetch_add(1, Ordering::SeqCst);
             }
-            continue;
-        } else if token.has_redirection() {
-            let mut proc = match redirect_cmd_execution(&mut token) {
-                Ok(p) => p,
+            let status = match execute(shell_name, &token) {
+                Ok(s) => s,
                 Err(e) => {
                     eprintln!("Error: {}", e);
-                    return;
+                    // like bash, a script stops at an unset variable with `set -u`
+                    if expand::is_unbound(&e) && (!signals::is_interactive() || sys::in_subshell()) {
+                        exit::leave(shell_name, 127);
+                    }
+                    1
                 }
             };
-
-            if let Ok(mut c) = proc.spawn() {
-                c.wait().unwrap();
-            } else {
-                eprintln!("Invalid: command not found!");
+            if tested {
+                TESTED.fetch_sub(1, Ordering::SeqCst);
             }
-        } else {
-            // execute command that has no redirection
-            let cmd = token.get_args();
-            if process::Command::new(&cmd[0])
-                                .args(&cmd[1..])
-                                .status().is_err() {
-                eprintln!("{}: command not found!", &cmd[0]);
+            vars::set_status(status);
+
+            if status != 0 && !tested && TESTED.load(Ordering::SeqCst) == 0 {
+                signals::run_trap(shell_name, signals::ERR);
+                if options::is_set("errexit") {
+                    exit::leave(shell_name, status);
+                }
             }
         }
+        signals::run_pending(shell_name);
+
+        // next command after `&&` runs only on success, after `||` on failure
+        run = match op.as_str() {
+            "&&" => vars::status() == 0,
+            "||" => vars::status() != 0,
+            _ => true,
+        };
+    }
+    vars::status()
+}
+
+/// Expand a single command and execute it, returning its exit status
+fn execute(shell_name: &mut ShellName, token: &Tokenizer) -> Result<i32, io::Error> {
+    // only substitutions of this command count for its status
+    expand::take_substitution_status();
+    let mut token = Tokenizer::new(&expand::expand_line(shell_name, token.remainder())?);
+
+    if token.is_empty() {
+        return Ok(0);
     }
+    trace(shell_name, &token)?;
+    if token.is_pipe() {
+        return piped_cmd_execution(shell_name, &mut token);
+    }
+    execute_simple(shell_name, &mut token)
 }
 
-// This function is used to execute shell defined functions
-fn execute_custom_fn(shell_name: &mut ShellName,
-                     token: &mut Tokenizer) -> Result<(), io::Error> {
-    match &token.peek()[0..] {
-        "cd" => cd::change_directory(shell_name, token),
-        "touch" | ">" => touch::touch(token)?,
-        _ => println!("Not implemented yet"),
+/// With `xtrace` on, print each expanded command of the pipeline
+/// to standard error after the expanded `PS4`, `+ ` by default
+fn trace(shell_name: &mut ShellName, token: &Tokenizer) -> Result<(), io::Error> {
+    if !options::is_set("xtrace") {
+        return Ok(());
+    }
+    let ps4 = vars::get("PS4").unwrap_or_else(|| "+ ".to_string());
+    let prefix = expand::expand_word(shell_name, &ps4)?;
+    for mut cmd in token.clone().get_multiple_tokens("|") {
+        // commands of a group are traced when each of them runs
+        if is_group(&cmd.peek_raw()) {
+            continue;
+        }
+        let (args, _) = redirect::split_redirections(&mut cmd)?;
+        let words: Vec<String> = args.iter().map(|a| tokenizer::quote_if_needed(a)).collect();
+        eprintln!("{}{}", prefix, words.join(" "));
     }
     Ok(())
 }
 
-/// If user supplies piped command this function splits it into
-/// two processes, executes them and pipes one being input to the pipe
-/// and the other being output from the pipe, which ends up displayed
-pub fn piped_cmd_execution(cmd_line: &mut Tokenizer) -> Result<(), io::Error> {
-    let mut tokens_before_pipe = cmd_line.commands_before_pipe();
+/// Execute a command which isn't a pipeline
+fn execute_simple(shell_name: &mut ShellName, token: &mut Tokenizer) -> Result<i32, io::Error> {
+    let first = token.peek_raw();
+    if first.starts_with("((") {
+        return Ok(arithmetic::arithmetic_command(&first));
+    }
+    if is_group(&first) {
+        return execute_group(shell_name, token);
+    }
+    if first == "{" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "syntax error: missing `}'",
+        ));
+    }
+    if vars::split_assignment(&first).is_some() {
+        let mut words = vec![];
+        while vars::split_assignment(&token.peek_raw()).is_some() {
+            words.extend(token.next());
+        }
+        if token.is_empty() {
+            return Ok(assign_variables(&words));
+        }
+        // `NAME=value command` sets the variables for the command only
+        let assignments: Vec<_> = words.iter().filter_map(|w| vars::split_assignment(w)).collect();
+        return vars::with_temporary(&assignments, || execute_simple(shell_name, token));
+    }
+
+    let (args, redirections) = redirect::split_redirections(token)?;
+    if args.is_empty() {
+        // redirections alone create, truncate or check the files
+        Redirections::open(&redirections)?;
+        return Ok(0);
+    }
+    if let Some(builtin) = customs::lookup(&args[0]) {
+        return run_builtin(shell_name, builtin, &args, &redirections, None);
+    }
 
-    let mut after_pipe_cmd: Vec<String> = vec![];
-    let mut before_pipe_cmd: Vec<String> = vec![];
+    run_program(shell_name, &args, &redirections)
+}
 
-    let mut proc = if cmd_line.has_redirection() {
-        redirect_cmd_execution(cmd_line)?
-    } else {
-        after_pipe_cmd = cmd_line.get_args();
-        // create a child process that has end of pipe open for stream
-        process::Command::new(&after_pipe_cmd[0])
+/// Run a program found in `PATH` and wait for it to finish
+pub fn run_program(shell_name: &mut ShellName, args: &[String],
+                   redirections: &[Redirection]) -> Result<i32, io::Error> {
+    let program = match hash::resolve(&args[0]) {
+        Ok(p) => p,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !args[0].contains('/') => {
+            return command_not_found(shell_name, args, redirections);
+        }
+        Err(e) => {
+            // the files are still created, like by a shell which forked first
+            Redirections::open(redirections)?;
+            return Ok(not_runnable(&args[0], &e));
+        }
     };
+    let mut proc = redirect_cmd_execution(&program, args, redirections)?;
+    match proc.status() {
+        Ok(s) => Ok(sys::exit_code(s)),
+        Err(e) => Ok(not_runnable(&args[0], &e)),
+    }
+}
 
-    // check if we have any arguments otherwise execute command
-    if after_pipe_cmd.is_empty() {
-        proc.args(&after_pipe_cmd[1..]);
+/// Report a program which can't be run. The status is 127
+/// when it doesn't exist and 126 when it can't be executed.
+fn not_runnable(name: &str, e: &io::Error) -> i32 {
+    match e.kind() {
+        io::ErrorKind::NotFound => {
+            eprintln!("{}: {}", name, e);
+            127
+        }
+        _ => {
+            eprintln!("{}: {}", name, e);
+            126
+        }
     }
-    let child = proc.stdin(process::Stdio::piped()).spawn()?;
+}
 
-    let mut proc2 = if tokens_before_pipe.has_redirection() {
-        redirect_cmd_execution(&mut tokens_before_pipe)?
-    } else {
-        before_pipe_cmd = tokens_before_pipe.get_args();
-        // create child process that redirects its output
-        // to the stdout end of the pipe.
-        // this will execute command and send output
-        // to the previously created process pipe.
-        process::Command::new(&before_pipe_cmd[0])
-    };
+/// A command which isn't found is passed to `command_not_found_handle`
+/// when it's defined. Otherwise similar names are suggested or, with
+/// `set -o correct`, the user is asked to run the closest one instead.
+fn command_not_found(shell_name: &mut ShellName, args: &[String],
+                     redirections: &[Redirection]) -> Result<i32, io::Error> {
+    if alias::get(NOT_FOUND_HANDLER).is_some() {
+        let words: Vec<String> = args.iter().map(|a| tokenizer::quote(a)).collect();
+        let line = format!("{} {}", NOT_FOUND_HANDLER, words.join(" "));
+        let saved = Redirections::open(redirections)?.apply()?;
+        let status = run_line(shell_name, &mut Tokenizer::new(&line));
+        saved.restore();
+        return Ok(status);
+    }
 
-    // check for arguments
-    if before_pipe_cmd.len() > 1 {
-        proc2.args(&before_pipe_cmd[1..]);
+    let suggestions = suggest::suggestions(&args[0]);
+    if let Some(closest) = suggestions.first().filter(|_| may_correct()) {
+        let prompt = format!("cr4sh_: correct '{}' to '{}' [y/N]? ", args[0], closest);
+        if confirm(&prompt)? {
+            let mut args = args.to_vec();
+            args[0] = closest.clone();
+            return match customs::lookup(&args[0]) {
+                Some(builtin) => run_builtin(shell_name, builtin, &args, redirections, None),
+                None => run_program(shell_name, &args, redirections),
+            };
+        }
     }
 
-    proc2.stdout(child.stdin.unwrap()).output()?;
-    Ok(())
+    // the files are still created, like by a shell which forked first
+    Redirections::open(redirections)?;
+    eprintln!("{}: command not found!", args[0]);
+    if !suggestions.is_empty() {
+        let names: Vec<String> = suggestions.iter().map(|s| format!("`{}'", s)).collect();
+        eprintln!("did you mean {}?", names.join(" or "));
+    }
+    Ok(127)
 }
 
-/// If the user command has stream redirection this function is used
-/// to accommodate that. This is done by creating a redirection and returning
-/// a command which can then be spawned as a child processes
-pub fn redirect_cmd_execution(cmd_line: &mut Tokenizer) 
-    -> Result<process::Command, io::Error> {
-    let mut redirection_count = [0; 2];
-    let args = cmd_line.args_before_redirection();
+/// the user is asked to correct typos when `correct` is set and
+/// there is someone at the terminal to answer
+fn may_correct() -> bool {
+    options::is_set("correct") && signals::is_interactive() && termion::is_tty(&io::stdin())
+}
 
-    // create process that will execute shell command
-    let mut proc = process::Command::new(&args[0]);
+/// Ask the user a yes or no question, no is the default
+fn confirm(prompt: &str) -> io::Result<bool> {
+    eprint!("{}", prompt);
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
 
-    proc.args(&args[1..]);
+/// Execute a subshell `( list )` in a forked child, so changes to the
+/// directory or variables don't leak out, or a group `{ list; }` in the
+/// current shell. Redirections after the group apply to all its commands.
+fn execute_group(shell_name: &mut ShellName, token: &mut Tokenizer) -> Result<i32, io::Error> {
+    let group = token.next_raw().unwrap_or_default();
+    let (args, redirections) = redirect::split_redirections(token)?;
+    if let Some(arg) = args.first() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("syntax error near unexpected token `{}'", arg),
+        ));
+    }
+    let redirections = Redirections::open(&redirections)?;
+
+    if let Some(inner) = group.strip_prefix('{') {
+        let inner = inner.strip_suffix('}').unwrap_or(inner);
+        let saved = redirections.apply()?;
+        let status = run_line(shell_name, &mut Tokenizer::new(inner));
+        saved.restore();
+        return Ok(status);
+    }
 
-    loop {
-        match cmd_line.next().as_deref() {
-            // command asked for append
-            Some(">>") => {
-                let f = append_stdout_file(&cmd_line.next().unwrap())?;
-                proc.stdout(f);
-                return Ok(proc);
-            },
-            Some("<") => {
-                redirection_count[0] += 1;
-
-                // retrieve file name if file/directory doesn't
-                // exist notify user and restart the shell
-                if let Some(name) = cmd_line.next() {
-                    // redirect stdin from a given file
-                    match open_stdin_file(&name) {
-                        Ok(f) => proc.stdin(f),
-                        Err(e) => return Err(e),
-                    };
-                }
-            },
-            Some(">") => {
-                redirection_count[1] += 1;
-
-                // redirect stdout to a give file
-                if let Some(name) = cmd_line.next() {
-                    match open_stdout_file(&name) {
-                        Ok(f) => proc.stdout(f),
-                        Err(e) => return Err(e),
-                    };
-                }
-            },
-            None => break,
-            _ => continue,
+    let inner = group
+        .strip_prefix('(')
+        .and_then(|g| g.strip_suffix(')'))
+        .ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "syntax error: missing `)'")
+        })?;
+    let child = sys::fork_child(|| {
+        if let Err(e) = redirections.perform() {
+            eprintln!("Error: {}", e);
+            return 1;
         }
+        let status = run_line(shell_name, &mut Tokenizer::new(inner));
+        signals::run_trap(shell_name, signals::EXIT);
+        status
+    })?;
+    sys::wait_for(child)
+}
+
+/// Command consisting of `NAME=value` words sets shell variables.
+/// Its status is the one of the last command substitution, if any.
+fn assign_variables(words: &[String]) -> i32 {
+    for (name, value) in words.iter().filter_map(|w| vars::split_assignment(w)) {
+        vars::set(name, value);
     }
+    expand::take_substitution_status().unwrap_or(0)
+}
 
-    // check flags that we don't have excessive number of redirection
-    if redirection_count[0] > 1 || redirection_count[1] > 1 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Invalid instructions for redirection",
-        ));
+/// Run a builtin in the shell itself with its redirections performed
+/// for the time it runs. `input` replaces its standard input, when
+/// it's the last command of a pipeline.
+fn run_builtin(shell_name: &mut ShellName, builtin: &dyn Builtin, args: &[String],
+               redirections: &[Redirection], input: Option<File>) -> Result<i32, io::Error> {
+    let mut redirections = Redirections::open(redirections)?;
+    if let Some(f) = input {
+        redirections.input_from(f)?;
     }
 
-    Ok(proc)
+    let mut ctx = Context { shell_name, keep_redirections: false };
+    let saved = redirections.apply()?;
+    let status = customs::with_std_io(|io| builtin.run(&mut ctx, args, io));
+    if ctx.keep_redirections {
+        saved.keep();
+    } else {
+        saved.restore();
+    }
+    Ok(status)
+}
+
+/// Command of a pipeline which was started
+enum Stage {
+    Process(process::Child),
+    Forked(Pid),
+    Done(i32),
+}
+
+impl Stage {
+    /// wait for the command to finish and return its exit status
+    fn wait(self) -> Result<i32, io::Error> {
+        match self {
+            Stage::Process(mut child) => Ok(sys::exit_code(child.wait()?)),
+            Stage::Forked(pid) => sys::wait_for(pid),
+            Stage::Done(status) => Ok(status),
+        }
+    }
 }
 
-fn append_stdout_file(file_name: &str) -> Result<File, io::Error> {
-    let f = OpenOptions::new()
-                            .append(true)
-                            .create(true)
-                            .open(file_name)?;
-    Ok(f)
+/// If user supplies piped command this function splits it into
+/// separate commands and runs all of them at the same time, with
+/// output of each command being the input of the next one through
+/// a pipe. Returns the exit status of the last command, or with
+/// `pipefail` on the status of the last command which failed.
+pub fn piped_cmd_execution(shell_name: &mut ShellName,
+                           cmd_line: &mut Tokenizer) -> Result<i32, io::Error> {
+    let commands = cmd_line.get_multiple_tokens("|");
+    let last = commands.len() - 1;
+
+    let mut stages = vec![];
+    let mut input: Option<File> = None;
+    for (i, mut cmd) in commands.into_iter().enumerate() {
+        let (reader, writer) = if i < last {
+            let (r, w) = pipe2(OFlag::O_CLOEXEC).map_err(sys::io_err)?;
+            unsafe { (Some(File::from_raw_fd(r)), Some(File::from_raw_fd(w))) }
+        } else {
+            (None, None)
+        };
+
+        // pipe ends are dropped as soon as the command has them
+        let stage = spawn_stage(shell_name, &mut cmd, input.take(), writer, reader.as_ref());
+        input = reader;
+
+        stages.push(stage.unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            Stage::Done(1)
+        }));
+    }
+
+    let mut statuses = vec![];
+    for stage in stages {
+        statuses.push(stage.wait()?);
+    }
+    let last = statuses.last().copied().unwrap_or(0);
+    if options::is_set("pipefail") {
+        return Ok(statuses.into_iter().rfind(|s| *s != 0).unwrap_or(0));
+    }
+    Ok(last)
 }
 
-/// Redirect a std out to a give file.
-/// If file doesn't exists create one
-fn open_stdout_file(file_name: &str) -> Result<File, io::Error> {
-    let file = OpenOptions::new()
-        .truncate(true)
-        .write(true)
-        .create(true)
-        .open(file_name)?;
-    Ok(file)
+/// Start a single command of a pipeline with the given input and output.
+/// Programs are spawned directly, other commands run in a forked shell,
+/// except a builtin at the end of the pipeline, which runs in the shell
+/// so `cd` or `set` take effect. `next` is the read end of the pipe to
+/// the next command, which a forked shell closes so the command gets
+/// `SIGPIPE` when the next one exits.
+fn spawn_stage(shell_name: &mut ShellName, cmd: &mut Tokenizer, input: Option<File>,
+               output: Option<File>, next: Option<&File>) -> Result<Stage, io::Error> {
+    let in_shell = |shell_name: &mut ShellName, cmd: &mut Tokenizer| {
+        if let Some(f) = next {
+            let _ = close(f.as_raw_fd());
+        }
+        if let Some(f) = &input {
+            let _ = dup2(f.as_raw_fd(), 0);
+        }
+        if let Some(f) = &output {
+            let _ = dup2(f.as_raw_fd(), 1);
+        }
+        execute_simple(shell_name, cmd).unwrap_or_else(|e| {
+            eprintln!("Error: {}", e);
+            1
+        })
+    };
+    if is_group(&cmd.peek_raw()) {
+        return Ok(Stage::Forked(sys::fork_child(|| in_shell(shell_name, cmd))?));
+    }
+
+    let (args, redirections) = redirect::split_redirections(&mut cmd.clone())?;
+    match args.first().map(|a| customs::lookup(a)) {
+        Some(Some(builtin)) if output.is_none() => {
+            let status = run_builtin(shell_name, builtin, &args, &redirections, input)?;
+            return Ok(Stage::Done(status));
+        }
+        Some(None) => {}
+        _ => return Ok(Stage::Forked(sys::fork_child(|| in_shell(shell_name, cmd))?)),
+    }
+
+    // the forked shell reports the error, or runs `command_not_found_handle`
+    let program = match hash::resolve(&args[0]) {
+        Ok(p) => p,
+        Err(_) => return Ok(Stage::Forked(sys::fork_child(|| in_shell(shell_name, cmd))?)),
+    };
+    let mut proc = redirect_cmd_execution(&program, &args, &redirections)?;
+    if let Some(f) = input {
+        proc.stdin(f);
+    }
+    if let Some(f) = output {
+        proc.stdout(f);
+    }
+
+    match proc.spawn() {
+        Ok(child) => Ok(Stage::Process(child)),
+        Err(e) => Ok(Stage::Done(not_runnable(&args[0], &e))),
+    }
 }
 
-/// Redirect a std in from a given file to console.
-/// If file doesn't exist error is thrown
-fn open_stdin_file(file_name: &str) -> Result<File, io::Error> {
-    let file = OpenOptions::new().read(true).open(file_name)?;
-    Ok(file)
+/// Create the process of a program. Files of the redirections are opened
+/// right away and the returned command rewires its descriptors, in the
+/// order redirections were written, in the child before the program
+/// is executed.
+pub fn redirect_cmd_execution(program: &Path, args: &[String], redirections: &[Redirection])
+    -> Result<process::Command, io::Error> {
+    let redirections = Redirections::open(redirections)?;
+
+    // create process that will execute shell command
+    let mut proc = process::Command::new(program);
+    proc.arg0(&args[0]).args(&args[1..]);
+    unsafe {
+        proc.pre_exec(move || {
+            signals::restore_defaults()?;
+            redirections.perform()
+        });
+    }
+    Ok(proc)
 }
 
 /// flushes text buffer to the stdout
@@ -263,21 +511,33 @@ fn write_to_stdout(text: &str) -> io::Result<()> {
     Ok(())
 }
 
-/// fetch the user inputted commands
-fn get_user_commands() -> Result<Tokenizer, io::Error> {
-    let mut input = String::new();
-
+/// Read the next line of commands, `None` at the end of the input
+fn get_user_commands(prompt: &str) -> Result<Option<Tokenizer>, io::Error> {
     // read user input
-    io::stdin().read_line(&mut input).unwrap();
+    let input = match editor::read_line(prompt)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
 
-    if input.trim().len() < 2 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Invalid command",
-        ));
-    }
-    if input.ends_with('\n') {
-        input.pop();
+    // lines following the command are read as here-document bodies
+    let input = heredoc::collect_heredocs(input.trim(), &mut || {
+        let prompt = vars::get("PS2").unwrap_or_else(|| "> ".to_string());
+        write_to_stdout(&prompt).ok()?;
+        sys::read_line(0).ok().flatten()
+    })?;
+    Ok(Some(Tokenizer::new(&input)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forked_stage_gets_sigpipe() {
+        let mut shell_name = ShellName::new("test");
+        for line in ["{ yes; } | head -1 > /dev/null", "( yes ) | head -1 > /dev/null"] {
+            let status = piped_cmd_execution(&mut shell_name, &mut Tokenizer::new(line));
+            assert_eq!(0, status.unwrap());
+        }
     }
-    Ok(Tokenizer::new(input.trim()))
 }
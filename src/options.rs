@@ -0,0 +1,61 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Shell options which can be changed with the `set` command,
+/// as pairs of the single letter flag and the long option name.
+/// Some options can only be set by their long name.
+pub const OPTIONS: &[(Option<char>, &str)] = &[
+    (None, "correct"),
+    (Some('e'), "errexit"),
+    (None, "ignoreeof"),
+    (Some('C'), "noclobber"),
+    // there is no pathname expansion yet, so this one changes nothing
+    (Some('f'), "noglob"),
+    (Some('u'), "nounset"),
+    (None, "pipefail"),
+    (Some('x'), "xtrace"),
+];
+
+lazy_static! {
+    /// Names of the options which are currently turned on
+    static ref ENABLED: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+}
+
+/// checks if the option with a given long name is turned on
+pub fn is_set(name: &str) -> bool {
+    ENABLED.lock().unwrap().contains(name)
+}
+
+/// Turn option on or off, returns false if there is no such option
+pub fn set(name: &str, on: bool) -> bool {
+    let name = match OPTIONS.iter().find(|(_, n)| *n == name) {
+        Some((_, n)) => *n,
+        None => return false,
+    };
+    let mut enabled = ENABLED.lock().unwrap();
+    if on {
+        enabled.insert(name);
+    } else {
+        enabled.remove(name);
+    }
+    true
+}
+
+/// long name of the option for a single letter flag
+pub fn by_letter(letter: char) -> Option<&'static str> {
+    OPTIONS.iter().find(|(c, _)| *c == Some(letter)).map(|(_, n)| *n)
+}
+
+/// Letters of the options which are on, the value of `$-`.
+/// An interactive shell also has `i`.
+pub fn flags() -> String {
+    let mut flags: String = OPTIONS
+        .iter()
+        .filter(|(_, name)| is_set(name))
+        .filter_map(|(c, _)| *c)
+        .collect();
+    if crate::signals::is_interactive() {
+        flags.push('i');
+    }
+    flags
+}
@@ -0,0 +1,411 @@
+use crate::tokenizer::{redirection_operator, unquote, Tokenizer};
+use crate::{heredoc, options, sys};
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::unistd::{close, dup2};
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+/// File descriptors opened for redirections are moved at or above this
+/// number, and above every descriptor the redirections target, so they
+/// don't collide with descriptors the user redirects.
+const FD_BASE: RawFd = 10;
+
+/// Single redirection of a file descriptor as written by the user
+#[derive(Clone, Debug, PartialEq)]
+pub enum Redirection {
+    /// `<` opens file for reading
+    Input(RawFd, String),
+    /// `>` truncates the file, with `true` when `>|` overrides noclobber
+    Output(RawFd, String, bool),
+    /// `>>` appends to the file
+    Append(RawFd, String),
+    /// `<>` opens file for reading and writing
+    ReadWrite(RawFd, String),
+    /// `n>&m` and `n<&m` make `n` a copy of `m`
+    Duplicate(RawFd, RawFd),
+    /// `n>&-` and `n<&-` close the descriptor
+    Close(RawFd),
+    /// `<<` and `<<<` feed text to the descriptor
+    Text(RawFd, String),
+}
+
+/// Split the remaining words of a command into its arguments and
+/// redirections. Redirections may appear anywhere in the command.
+pub fn split_redirections(line: &mut Tokenizer) -> io::Result<(Vec<String>, Vec<Redirection>)> {
+    let mut args = vec![];
+    let mut redirections = vec![];
+
+    while let Some(word) = line.next_raw() {
+        let (fd, op, target) = match redirection_operator(&word) {
+            Some(r) => r,
+            None => {
+                args.push(unquote(&word));
+                continue;
+            }
+        };
+
+        let target = if target.is_empty() {
+            line.next().ok_or_else(|| {
+                io::Error::new(ErrorKind::InvalidInput, "syntax error near unexpected token `newline'")
+            })?
+        } else {
+            unquote(target)
+        };
+        redirections.extend(parse(fd, op, target)?);
+    }
+    Ok((args, redirections))
+}
+
+fn parse(fd: Option<RawFd>, op: &str, target: String) -> io::Result<Vec<Redirection>> {
+    let input = fd.unwrap_or(0);
+    let output = fd.unwrap_or(1);
+
+    Ok(match op {
+        "<" => vec![Redirection::Input(input, target)],
+        "<>" => vec![Redirection::ReadWrite(input, target)],
+        "<<" => vec![Redirection::Text(input, target)],
+        "<<<" => vec![Redirection::Text(input, target + "\n")],
+        ">" => vec![Redirection::Output(output, target, false)],
+        ">|" => vec![Redirection::Output(output, target, true)],
+        ">>" => vec![Redirection::Append(output, target)],
+        "&>" => vec![
+            Redirection::Output(1, target, false),
+            Redirection::Duplicate(2, 1),
+        ],
+        "&>>" => vec![Redirection::Append(1, target), Redirection::Duplicate(2, 1)],
+        ">&" | "<&" => {
+            let fd = if op == ">&" { output } else { input };
+            if target == "-" {
+                vec![Redirection::Close(fd)]
+            } else if let Ok(src) = target.parse() {
+                vec![Redirection::Duplicate(fd, src)]
+            } else if op == ">&" && fd == 1 {
+                // `>&file` is the same as `&>file`
+                vec![Redirection::Output(1, target, false), Redirection::Duplicate(2, 1)]
+            } else {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{}: ambiguous redirect", target),
+                ));
+            }
+        }
+        _ => unreachable!("unknown redirection operator {}", op),
+    })
+}
+
+/// Redirections with their files opened, ready to be
+/// performed in the order they were written
+pub struct Redirections {
+    actions: Vec<Action>,
+    // lowest descriptor files and saved copies are moved to
+    base: RawFd,
+    // keeps opened files alive until redirections are dropped
+    _files: Vec<File>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Action {
+    Dup(RawFd, RawFd),
+    Close(RawFd),
+}
+
+impl Redirections {
+    /// Open every file used by the redirections. Errors, like a missing
+    /// input file or a bad descriptor, are reported before any command runs.
+    pub fn open(redirections: &[Redirection]) -> io::Result<Self> {
+        let mut actions = vec![];
+        let mut files = vec![];
+        let base = redirections.iter().map(target).max().map_or(FD_BASE, |fd| FD_BASE.max(fd + 1));
+        // descriptors which will be open at this point of the redirections
+        let mut opened: Vec<(RawFd, bool)> = vec![];
+
+        for r in redirections {
+            let action = match r {
+                Redirection::Duplicate(fd, src) => {
+                    let valid = match opened.iter().rev().find(|(f, _)| f == src) {
+                        Some((_, open)) => *open,
+                        None => fcntl(*src, FcntlArg::F_GETFD).is_ok(),
+                    };
+                    if !valid {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("{}: Bad file descriptor", src),
+                        ));
+                    }
+                    Action::Dup(*src, *fd)
+                }
+                Redirection::Close(fd) => Action::Close(*fd),
+                _ => {
+                    let (fd, file) = open_file(r)?;
+                    let high = move_high(&file, base)?;
+                    let action = Action::Dup(high.as_raw_fd(), fd);
+                    files.push(high);
+                    action
+                }
+            };
+            opened.push(match action {
+                Action::Dup(_, fd) => (fd, true),
+                Action::Close(fd) => (fd, false),
+            });
+            actions.push(action);
+        }
+        Ok(Redirections { actions, base, _files: files })
+    }
+
+    /// Read standard input from the file before other redirections
+    /// are performed, e.g. from the pipe of a preceding command.
+    pub fn input_from(&mut self, file: File) -> io::Result<()> {
+        let high = move_high(&file, self.base)?;
+        self.actions.insert(0, Action::Dup(high.as_raw_fd(), 0));
+        self._files.push(high);
+        Ok(())
+    }
+
+    /// Rewire the file descriptors of the current process. This only
+    /// calls `dup2`, `close` and `fcntl`, so it's safe to run in a forked child.
+    pub fn perform(&self) -> io::Result<()> {
+        for action in self.actions.iter() {
+            match *action {
+                Action::Dup(src, fd) if src != fd => {
+                    dup2(src, fd).map_err(sys::io_err)?;
+                }
+                Action::Dup(fd, _) => {
+                    // `n>&n` keeps the descriptor, also in executed programs
+                    fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty())).map_err(sys::io_err)?;
+                }
+                Action::Close(fd) => {
+                    let _ = close(fd);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Perform redirections in the shell itself, e.g. for a group command.
+    /// The returned descriptors have to be restored afterwards.
+    pub fn apply(&self) -> io::Result<SavedFds> {
+        io::stdout().flush()?;
+
+        let mut saved = SavedFds(vec![]);
+        for action in self.actions.iter() {
+            let fd = match *action {
+                Action::Dup(_, fd) | Action::Close(fd) => fd,
+            };
+            if saved.0.iter().all(|(f, _)| *f != fd) {
+                let copy = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(self.base)).ok();
+                saved.0.push((fd, copy));
+            }
+        }
+
+        if let Err(e) = self.perform() {
+            saved.restore();
+            return Err(e);
+        }
+        Ok(saved)
+    }
+}
+
+/// Copies of the descriptors which were replaced by redirections
+/// performed in the shell. `None` means it wasn't open before.
+pub struct SavedFds(Vec<(RawFd, Option<RawFd>)>);
+
+impl SavedFds {
+    /// put back the original descriptors
+    pub fn restore(self) {
+        let _ = io::stdout().flush();
+        for (fd, copy) in self.0.into_iter().rev() {
+            match copy {
+                Some(copy) => {
+                    let _ = dup2(copy, fd);
+                    let _ = close(copy);
+                }
+                None => {
+                    let _ = close(fd);
+                }
+            }
+        }
+    }
+
+    /// Leave the redirections in effect, like `exec` does,
+    /// and drop the copies of the original descriptors
+    pub fn keep(self) {
+        let _ = io::stdout().flush();
+        for copy in self.0.into_iter().filter_map(|(_, copy)| copy) {
+            let _ = close(copy);
+        }
+    }
+}
+
+/// copy the file to a descriptor which doesn't collide with redirected ones
+fn move_high(file: &File, base: RawFd) -> io::Result<File> {
+    let high = fcntl(file.as_raw_fd(), FcntlArg::F_DUPFD_CLOEXEC(base)).map_err(sys::io_err)?;
+    Ok(unsafe { File::from_raw_fd(high) })
+}
+
+/// descriptor changed by the redirection
+fn target(r: &Redirection) -> RawFd {
+    match r {
+        Redirection::Input(fd, _)
+        | Redirection::Output(fd, _, _)
+        | Redirection::Append(fd, _)
+        | Redirection::ReadWrite(fd, _)
+        | Redirection::Duplicate(fd, _)
+        | Redirection::Close(fd)
+        | Redirection::Text(fd, _) => *fd,
+    }
+}
+
+/// open the file for a redirection and return it with the target descriptor
+fn open_file(r: &Redirection) -> io::Result<(RawFd, File)> {
+    let mut opts = OpenOptions::new();
+    let (fd, name) = match r {
+        Redirection::Input(fd, name) => {
+            opts.read(true);
+            (*fd, name)
+        }
+        Redirection::Output(fd, name, force) => {
+            if !force && options::is_set("noclobber") && is_regular_file(name) {
+                return Err(io::Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("{}: cannot overwrite existing file", name),
+                ));
+            }
+            opts.write(true).create(true).truncate(true);
+            (*fd, name)
+        }
+        Redirection::Append(fd, name) => {
+            opts.append(true).create(true);
+            (*fd, name)
+        }
+        Redirection::ReadWrite(fd, name) => {
+            opts.read(true).write(true).create(true);
+            (*fd, name)
+        }
+        Redirection::Text(fd, text) => return Ok((*fd, heredoc::open_document(text)?)),
+        Redirection::Duplicate(..) | Redirection::Close(..) => unreachable!(),
+    };
+
+    let file = opts
+        .mode(0o666)
+        .open(name)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", name, e)))?;
+    Ok((fd, file))
+}
+
+fn is_regular_file(name: &str) -> bool {
+    std::fs::metadata(name).map(|m| m.is_file()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    fn redirections(line: &str) -> (Vec<String>, Vec<Redirection>) {
+        split_redirections(&mut Tokenizer::new(line)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_redirections() {
+        let (args, r) = redirections("cmd 2>&1 a >> out 3< in b 4>&- &> both");
+        assert_eq!(vec!["cmd", "a", "b"], args);
+        assert_eq!(
+            vec![
+                Redirection::Duplicate(2, 1),
+                Redirection::Append(1, "out".to_string()),
+                Redirection::Input(3, "in".to_string()),
+                Redirection::Close(4),
+                Redirection::Output(1, "both".to_string(), false),
+                Redirection::Duplicate(2, 1),
+            ],
+            r
+        );
+    }
+
+    #[test]
+    fn test_parse_separated_targets() {
+        let (args, r) = redirections("cat <> 'my file' >| out <<< text");
+        assert_eq!(vec!["cat"], args);
+        assert_eq!(
+            vec![
+                Redirection::ReadWrite(0, "my file".to_string()),
+                Redirection::Output(1, "out".to_string(), true),
+                Redirection::Text(0, "text\n".to_string()),
+            ],
+            r
+        );
+    }
+
+    #[test]
+    fn test_quoted_operator_is_argument() {
+        let (args, r) = redirections("echo '>' \"2>&1\"");
+        assert_eq!(vec!["echo", ">", "2>&1"], args);
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn test_bad_descriptor() {
+        let (_, r) = redirections("cmd 2>&97");
+        assert!(Redirections::open(&r).is_err());
+        let (_, r) = redirections("cmd 2>&1 1>&-");
+        assert!(Redirections::open(&r).is_ok());
+    }
+
+    #[test]
+    fn test_apply_and_restore() {
+        let name = "redirect_test_apply.txt";
+        let (_, r) = redirections(&format!("7> {}", name));
+        let saved = Redirections::open(&r).unwrap().apply().unwrap();
+        assert!(fcntl(7, FcntlArg::F_GETFD).is_ok());
+        saved.restore();
+        assert!(fcntl(7, FcntlArg::F_GETFD).is_err());
+        fs::remove_file(name).unwrap();
+    }
+
+    #[test]
+    fn test_apply_and_keep() {
+        let name = "redirect_test_keep.txt";
+        let (_, r) = redirections(&format!("8> {}", name));
+        Redirections::open(&r).unwrap().apply().unwrap().keep();
+        assert!(fcntl(8, FcntlArg::F_GETFD).is_ok());
+        close(8).unwrap();
+        fs::remove_file(name).unwrap();
+    }
+
+    #[test]
+    fn test_keep_descriptor_above_base() {
+        let name = "redirect_test_fd10.txt";
+        let (_, r) = redirections(&format!("10> {}", name));
+        Redirections::open(&r).unwrap().apply().unwrap().keep();
+        assert_eq!(Ok(0), fcntl(10, FcntlArg::F_GETFD));
+
+        let (_, r) = redirections("9>&10");
+        let saved = Redirections::open(&r).unwrap().apply().unwrap();
+        nix::unistd::write(9, b"x\n").unwrap();
+        saved.restore();
+        close(10).unwrap();
+        assert_eq!("x\n", fs::read_to_string(name).unwrap());
+        fs::remove_file(name).unwrap();
+    }
+
+    #[test]
+    fn test_stderr_to_file_left_to_right() {
+        let name = "redirect_test_out.txt";
+        let (args, r) = redirections(&format!(
+            "sh -c 'echo out; echo err >&2' > {} 2>&1",
+            name
+        ));
+        let redirections = Redirections::open(&r).unwrap();
+        let mut proc = Command::new(&args[0]);
+        proc.args(&args[1..]);
+        unsafe { proc.pre_exec(move || redirections.perform()) };
+        proc.status().unwrap();
+
+        assert_eq!("out\nerr\n", fs::read_to_string(name).unwrap());
+        fs::remove_file(name).unwrap();
+    }
+}
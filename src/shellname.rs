@@ -41,7 +41,7 @@ impl ShellName {
         self.shell_name = format!(
                 "{}┌{}{}:{} {}\n{}└─> {}{}§ {}{}",
                 color::Fg(color::Red),
-                self.name.to_string(),
+                self.name,
                 color::Fg(color::Cyan),
                 &self.current_dir,
                 color::Fg(color::Reset),
@@ -0,0 +1,270 @@
+use crate::shellname::ShellName;
+use crate::sys::io_err;
+use crate::tokenizer::Tokenizer;
+use crate::vars;
+use nix::sys::signal::{signal, SigHandler, Signal};
+use nix::unistd::{getpid, Pid};
+use signal_hook::consts::FORBIDDEN;
+use signal_hook::iterator::{Handle, Signals};
+use signal_hook::low_level::emulate_default_handler;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::io;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// Signals an interactive shell ignores, so Ctrl-C, Ctrl-\ and Ctrl-Z
+/// only reach the command running in the foreground
+const IGNORED: [Signal; 4] = [Signal::SIGINT, Signal::SIGQUIT, Signal::SIGTSTP, Signal::SIGTTOU];
+
+/// Ignored signals whose default action children get back. Without job
+/// control a stopped command could never be continued and the shell
+/// would wait for it forever, so Ctrl-Z stays ignored in children.
+const RESTORED: [Signal; 2] = [Signal::SIGINT, Signal::SIGQUIT];
+
+/// Pseudo-signal whose trap runs when the shell exits
+pub const EXIT: i32 = 0;
+/// Pseudo-signal whose trap runs before every command
+pub const DEBUG: i32 = 65;
+/// Pseudo-signal whose trap runs after a command failed
+pub const ERR: i32 = 66;
+/// Pseudo-signal whose trap runs when a sourced script finishes
+pub const RETURN: i32 = 67;
+
+const PSEUDO: [(&str, i32); 4] = [("EXIT", EXIT), ("DEBUG", DEBUG), ("ERR", ERR), ("RETURN", RETURN)];
+
+lazy_static! {
+    /// Commands of the traps by signal number, an empty one ignores the signal
+    static ref TRAPS: Mutex<BTreeMap<i32, String>> = Mutex::new(BTreeMap::new());
+
+    /// Handle of the thread which receives trapped signals and the
+    /// process it runs in, a forked shell needs its own one
+    static ref RELAY: Mutex<Option<(Pid, Handle)>> = Mutex::new(None);
+}
+
+/// Bit set of the signals which were caught and whose traps have to run
+static PENDING: AtomicU64 = AtomicU64::new(0);
+/// Bit set of the signals which were ever trapped. Once `signal_hook`
+/// handles a signal it keeps doing so and the relay emulates what
+/// the signal would do without a trap.
+static REGISTERED: AtomicU64 = AtomicU64::new(0);
+static INTERACTIVE: AtomicBool = AtomicBool::new(false);
+/// set while a trap runs, traps aren't run from inside one
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Ignore the keyboard signals in an interactive shell.
+/// Ctrl-C at the prompt is read as a key by the line editor.
+pub fn ignore_interactive() -> io::Result<()> {
+    INTERACTIVE.store(true, Ordering::SeqCst);
+    for sig in IGNORED.iter() {
+        unsafe { signal(*sig, SigHandler::SigIgn) }.map_err(io_err)?;
+    }
+    Ok(())
+}
+
+/// Restore the default action of SIGINT and SIGQUIT. Ignored signals
+/// stay ignored across `exec`, so this has to be done in every child
+/// before it runs a command. It's safe to call after a fork.
+pub fn restore_defaults() -> io::Result<()> {
+    let registered = REGISTERED.load(Ordering::SeqCst);
+    for sig in RESTORED.iter().filter(|s| registered & bit(**s as i32) == 0) {
+        unsafe { signal(*sig, SigHandler::SigDfl) }.map_err(io_err)?;
+    }
+    Ok(())
+}
+
+/// Forget the traps in a forked shell, only ignored signals stay ignored.
+/// The child gets its own relay for the signals which are handled.
+pub fn reset_in_child() {
+    TRAPS.lock().unwrap().retain(|id, command| command.is_empty() && !is_pseudo(*id));
+    PENDING.store(0, Ordering::SeqCst);
+    *RELAY.lock().unwrap() = None;
+
+    let registered = REGISTERED.load(Ordering::SeqCst);
+    for sig in (1..64).filter(|s| registered & bit(*s) != 0) {
+        let _ = relay(sig);
+    }
+}
+
+/// Description of a signal which terminated a command, like `SIGSEGV`
+pub fn name(sig: i32) -> String {
+    match Signal::try_from(sig) {
+        Ok(s) => s.as_str().to_string(),
+        Err(_) => format!("signal {}", sig),
+    }
+}
+
+/// Number of a signal or pseudo-signal given by its number
+/// or its name, with or without the `SIG` prefix
+pub fn parse(spec: &str) -> Option<i32> {
+    if let Ok(n) = spec.parse::<i32>() {
+        return Some(n).filter(|n| *n == EXIT || Signal::try_from(*n).is_ok());
+    }
+    let spec = spec.to_uppercase();
+    let spec = spec.strip_prefix("SIG").unwrap_or(&spec);
+    match PSEUDO.iter().find(|(name, _)| *name == spec) {
+        Some((_, id)) => Some(*id),
+        None => Signal::from_str(&format!("SIG{}", spec)).ok().map(|s| s as i32),
+    }
+}
+
+/// name a trap is listed by, `SIGINT` or `EXIT`
+fn trap_name(id: i32) -> String {
+    match PSEUDO.iter().find(|(_, i)| *i == id) {
+        Some((name, _)) => name.to_string(),
+        None => name(id),
+    }
+}
+
+fn is_pseudo(id: i32) -> bool {
+    PSEUDO.iter().any(|(_, i)| *i == id)
+}
+
+fn bit(sig: i32) -> u64 {
+    1 << sig
+}
+
+/// Set the command run when the signal arrives, an empty one ignores
+/// it and `None` restores what the shell does without a trap
+pub fn set_trap(id: i32, command: Option<&str>) -> io::Result<()> {
+    if FORBIDDEN.contains(&id) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot be trapped"));
+    }
+    match command {
+        Some(c) => TRAPS.lock().unwrap().insert(id, c.to_string()),
+        None => TRAPS.lock().unwrap().remove(&id),
+    };
+    if is_pseudo(id) || REGISTERED.load(Ordering::SeqCst) & bit(id) != 0 {
+        return Ok(());
+    }
+
+    let handler = match command {
+        Some("") => SigHandler::SigIgn,
+        Some(_) => return relay(id),
+        None if is_ignored(id) => SigHandler::SigIgn,
+        None => SigHandler::SigDfl,
+    };
+    let sig = Signal::try_from(id).map_err(io_err)?;
+    unsafe { signal(sig, handler) }.map_err(io_err)?;
+    Ok(())
+}
+
+/// Traps with their names and commands, ordered by signal number
+pub fn traps() -> Vec<(i32, String, String)> {
+    TRAPS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, c)| (*id, trap_name(*id), c.clone()))
+        .collect()
+}
+
+/// Command of the trap of the signal
+pub fn get_trap(id: i32) -> Option<String> {
+    TRAPS.lock().unwrap().get(&id).cloned()
+}
+
+/// checks if the shell reads commands from a terminal
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::SeqCst)
+}
+
+/// signals the interactive shell ignores when they aren't trapped
+fn is_ignored(sig: i32) -> bool {
+    is_interactive() && IGNORED.iter().any(|s| *s as i32 == sig)
+}
+
+/// Let the relay thread receive the signal, it's started
+/// when the first signal is trapped
+fn relay(sig: i32) -> io::Result<()> {
+    let mut relay = RELAY.lock().unwrap();
+    if relay.as_ref().map(|(pid, _)| *pid) != Some(getpid()) {
+        let mut signals = Signals::new(&[] as &[i32])?;
+        *relay = Some((getpid(), signals.handle()));
+        thread::spawn(move || {
+            for sig in signals.forever() {
+                dispatch(sig);
+            }
+        });
+    }
+    if let Some((_, handle)) = relay.as_ref() {
+        handle.add_signal(sig)?;
+    }
+    REGISTERED.fetch_or(bit(sig), Ordering::SeqCst);
+    Ok(())
+}
+
+/// Queue the trap of a signal, which runs between commands on the main
+/// thread. Without a trap the signal does what it would do to the shell.
+fn dispatch(sig: i32) {
+    match TRAPS.lock().unwrap().get(&sig) {
+        Some(c) if !c.is_empty() => {
+            PENDING.fetch_or(bit(sig), Ordering::SeqCst);
+        }
+        Some(_) => {}
+        None if is_ignored(sig) => {}
+        None => {
+            let _ = emulate_default_handler(sig);
+        }
+    }
+}
+
+/// Run the traps of the signals caught since the last call
+pub fn run_pending(shell_name: &mut ShellName) {
+    if RUNNING.load(Ordering::SeqCst) {
+        return;
+    }
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    for sig in (1..64).filter(|s| pending & bit(*s) != 0) {
+        run_trap(shell_name, sig);
+    }
+}
+
+/// Run the trap of a signal or pseudo-signal, if it has one. `$?` is
+/// kept as it was before the trap and traps don't trigger other traps.
+pub fn run_trap(shell_name: &mut ShellName, id: i32) {
+    let command = match get_trap(id) {
+        Some(c) if !c.is_empty() => c,
+        _ => return,
+    };
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let status = vars::status();
+    crate::run_line(shell_name, &mut Tokenizer::new(&command));
+    vars::set_status(status);
+    RUNNING.store(false, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sys;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    #[test]
+    fn test_children_get_default_actions() {
+        // a forked copy ignores the signals, so the other tests don't
+        let child = sys::fork_child(|| {
+            if ignore_interactive().is_err() {
+                return 1;
+            }
+            let mut proc = Command::new("sh");
+            proc.args(["-c", "kill -TSTP $$; kill -INT $$; exit 3"]);
+            let ignored = proc.status().map(sys::exit_code);
+            unsafe { proc.pre_exec(restore_defaults) };
+            // SIGTSTP stays ignored, the command isn't stopped
+            let restored = proc.status().map(sys::exit_code);
+            match (ignored, restored) {
+                (Ok(3), Ok(130)) => 0,
+                _ => 2,
+            }
+        });
+        assert_eq!(0, sys::wait_for(child.unwrap()).unwrap());
+        assert!(!is_interactive());
+        assert_eq!("SIGTERM", name(15));
+    }
+}
@@ -0,0 +1,76 @@
+use crate::customs::{self, alias, hash};
+
+/// Suggested names are at most this many edits away
+/// from a short name, longer names may have one more
+const MAX_DISTANCE: usize = 1;
+
+/// Builtins, aliases and programs whose names are closest to the
+/// mistyped one, at most three of them in alphabetical order
+pub fn suggestions(name: &str) -> Vec<String> {
+    let max = if name.chars().count() < 6 { MAX_DISTANCE } else { MAX_DISTANCE + 1 };
+    let mut candidates: Vec<String> = customs::builtins().map(|b| b.name().to_string()).collect();
+    candidates.extend(alias::names());
+    candidates.extend(hash::programs());
+
+    let mut best = vec![];
+    let mut best_distance = max + 1;
+    for candidate in candidates {
+        let d = distance(name, &candidate);
+        if d < best_distance {
+            best_distance = d;
+            best.clear();
+        }
+        if d == best_distance && d > 0 {
+            best.push(candidate);
+        }
+    }
+    best.sort();
+    best.dedup();
+    best.truncate(3);
+    best
+}
+
+/// Number of inserted, deleted, replaced or swapped
+/// neighbouring characters which turn `a` into `b`
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j] is the distance of the first i characters of a and j of b
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(1, distance("grpe", "grep"));
+        assert_eq!(1, distance("sl", "ls"));
+        assert_eq!(2, distance("pusdh", "pushd "));
+        assert_eq!(3, distance("", "cat"));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(vec!["pushd"], suggestions("puhsd"));
+        assert!(suggestions("histroy").contains(&"history".to_string()));
+        assert!(suggestions("qqqqqqqqqq").is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::signals;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::errno::Errno;
+use nix::unistd::{fork, read, ForkResult, Pid};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::os::unix::io::RawFd;
+use std::os::unix::process::ExitStatusExt;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// set in processes forked from the shell
+static SUBSHELL: AtomicBool = AtomicBool::new(false);
+
+/// Convert an error returned by `nix` into `io::Error`
+pub fn io_err(e: nix::Error) -> io::Error {
+    match e.as_errno() {
+        Some(errno) => errno.into(),
+        None => io::Error::other(e),
+    }
+}
+
+/// Read a single byte from the descriptor without buffering, so nothing
+/// after it is taken from the input of commands run later. `None` at
+/// the end of the input.
+pub fn read_byte(fd: RawFd) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    loop {
+        match read(fd, &mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(nix::Error::Sys(Errno::EINTR)) => continue,
+            Err(e) => return Err(io_err(e)),
+        }
+    }
+}
+
+/// Read a line, with its newline, from the descriptor a byte at a time.
+/// `None` at the end of the input.
+pub fn read_line(fd: RawFd) -> io::Result<Option<String>> {
+    let mut line = vec![];
+    while let Some(byte) = read_byte(fd)? {
+        line.push(byte);
+        if byte == b'\n' {
+            break;
+        }
+    }
+    if line.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+/// Fork the shell and run `f` in the child process,
+/// the child exits with the status returned by `f`.
+pub fn fork_child<F: FnOnce() -> i32>(f: F) -> io::Result<Pid> {
+    // anything left in the buffers would be written twice
+    io::stdout().flush()?;
+    io::stderr().flush()?;
+
+    match unsafe { fork() }.map_err(io_err)? {
+        ForkResult::Child => {
+            SUBSHELL.store(true, Ordering::SeqCst);
+            let _ = signals::restore_defaults();
+            signals::reset_in_child();
+            let status = f();
+            let _ = io::stdout().flush();
+            process::exit(status);
+        }
+        ForkResult::Parent { child } => Ok(child),
+    }
+}
+
+/// checks if this is a forked copy of the shell
+pub fn in_subshell() -> bool {
+    SUBSHELL.load(Ordering::SeqCst)
+}
+
+/// Wait for the child process to terminate and return its exit status
+pub fn wait_for(pid: Pid) -> io::Result<i32> {
+    loop {
+        match waitpid(pid, None) {
+            Ok(WaitStatus::Exited(_, code)) => return Ok(code),
+            Ok(WaitStatus::Signaled(_, sig, core)) => return Ok(signaled(sig as i32, core)),
+            Ok(_) => continue,
+            Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => continue,
+            Err(e) => return Err(io_err(e)),
+        }
+    }
+}
+
+/// Exit status of a finished command, 128 plus the number
+/// of the signal when it was killed by one
+pub fn exit_code(status: process::ExitStatus) -> i32 {
+    match status.signal() {
+        Some(sig) => signaled(sig, status.core_dumped()),
+        None => status.code().unwrap_or(1),
+    }
+}
+
+/// Report a command killed by a signal and return its exit status.
+/// An interrupt only ends the line, a broken pipe is expected in
+/// pipelines and neither is reported.
+fn signaled(sig: i32, core_dumped: bool) -> i32 {
+    match Signal::try_from(sig) {
+        Ok(Signal::SIGINT) => eprintln!(),
+        Ok(Signal::SIGPIPE) => {}
+        _ => eprintln!(
+            "killed by signal {} ({}){}",
+            sig,
+            signals::name(sig),
+            if core_dumped { " (core dumped)" } else { "" }
+        ),
+    }
+    128 + sig
+}
@@ -13,8 +13,9 @@ pub struct Tokenizer {
 impl Tokenizer {
     /// constructor
     pub fn new(line: &str) -> Self {
-        let is_pipe = line.contains(" | ");
-        let has_redirection = line.contains(" >") || line.contains(" < ");
+        let words = raw_words(line);
+        let is_pipe = words.iter().any(|w| w == "|");
+        let has_redirection = words.iter().any(|w| is_redirection(w));
 
         Tokenizer {
             current: Some(line.to_string()),
@@ -23,46 +24,11 @@ impl Tokenizer {
         }
     }
 
-    /// If the `current` line contains redirection ">" or "<"
-    /// returns all tokens before redirection as a vector of strings.
-    /// Else is found returns all the tokens as vector of strings.
-    /// This method call consumes tokens from `current`
-    pub fn args_before_redirection(&mut self) -> Vec<String> {
-        if !self.has_redirection() {
-            return self.get_args()
-        }
-
-        let mut args = vec![];
-        while self.current.is_some() {
-            if self.peek().eq(">") ||
-            self.peek().eq("<") ||
-            self.peek().eq(">>") {
-                break;
-            } else {
-                args.push(self.next().unwrap());
-            }
-        }
-        args
-    }
-
-    /// If the `current` line has a pipe symbol "|" in it
-    /// this method will return part of string string before
-    /// that symbol as a new Tokenizer object. This method consumes
-    /// `current` line, so if no pipe symbol is found, it will
-    /// reconstruct a new Tokenizer while disposing current one.
-    pub fn commands_before_pipe(&mut self) -> Tokenizer {
-        if !self.is_pipe() {
-            self.clone()
-        } else {
-            self._split_tokenizer("|")
-        }
-    }
-
     /// get all the argument from the `current` line
     /// and return as a vector of strings.
     pub fn get_args(&mut self) -> Vec<String> {
         let mut args = vec![];
-        while let Some(a) = self.next() {
+        for a in self.by_ref() {
             if a.eq("&&") {
                 break;
             }
@@ -93,12 +59,7 @@ impl Tokenizer {
     /// Check if the current lines starts with a given prefix
     pub fn starts_with(&self, prefix: &str) -> bool {
         if let Some(cur) = self.current.as_ref() {
-            if prefix.len() > cur.len() {
-                return false
-            }
-            if prefix.eq(&cur[..prefix.len()]) {
-                return true
-            }
+            return cur.starts_with(prefix)
         }
         false
     }
@@ -106,20 +67,24 @@ impl Tokenizer {
     /// peek what is the next token without consuming it.
     /// this returns a copy of the next token.
     pub fn peek(&self) -> String {
-        let mut res = String::new();
-        if let Some(cur) = self.current.as_deref() {
-            let mut open = 0u8;
-            for c in cur.chars().into_iter() {
-                if c.eq(&'"') || c.eq(&'\'') {
-                open = open ^ 1;
-                } else if c.eq(&' ') && open == 0 {
-                    break;
-                } else {
-                    res.push(c);
-                }
+        unquote(&self.peek_raw())
+    }
+
+    /// peek the next token exactly as it was typed,
+    /// with quotation marks and escapes preserved.
+    pub fn peek_raw(&self) -> String {
+        match self.current.as_deref() {
+            Some(cur) => {
+                let cur = cur.trim_start();
+                cur[..word_end(cur)].to_string()
             }
+            None => String::new(),
         }
-        res
+    }
+
+    /// Returns the unconsumed part of the line as it was typed
+    pub fn remainder(&self) -> &str {
+        self.current.as_deref().unwrap_or("")
     }
 
     pub fn get_multiple_tokens(&mut self, pattern: &str) -> Vec<Tokenizer> {
@@ -132,19 +97,13 @@ impl Tokenizer {
 
     pub fn _split_tokenizer(&mut self, pattern: &str) -> Tokenizer {
         let mut before = String::new();
-        while let Some(a) = self.next() {
+        // words are kept as they were typed so quotation
+        // marks are preserved in a new Tokenizer
+        while let Some(a) = self.next_raw() {
             if a.eq(pattern) {
                 break;
             }
-            // if next value has space in it, that means user supplied
-            // text in quotation marks, & we preserve it in a new Tokenizer
-            if a.contains(' ') {
-                before.push('\'');
-                before.push_str(&a);
-                before.push('\'');
-            } else {
-                before.push_str(&a);
-            }
+            before.push_str(&a);
             before.push(' ');
         }
         before.pop();
@@ -154,43 +113,291 @@ impl Tokenizer {
     pub fn is_empty(&self) -> bool {
         self.current.is_none()
     }
+
+    /// Split the line on `;`, `&&` and `||` into separate commands,
+    /// each paired with the operator which follows it.
+    pub fn command_list(&mut self) -> Vec<(Tokenizer, String)> {
+        let mut list = vec![];
+        let mut cmd = String::new();
+        loop {
+            let word = self.next_raw();
+            let op = match word.as_deref() {
+                Some(w) if !matches!(w, ";" | "&&" | "||") => {
+                    cmd.push_str(w);
+                    cmd.push(' ');
+                    continue;
+                }
+                Some(op) => op,
+                None => ";",
+            };
+
+            if !cmd.is_empty() {
+                cmd.pop();
+                list.push((Tokenizer::new(&cmd), op.to_string()));
+                cmd.clear();
+            }
+            if word.is_none() {
+                return list;
+            }
+        }
+    }
+
+    /// consume the next token without removing quotation marks
+    pub fn next_raw(&mut self) -> Option<String> {
+        let cur = self.current.take()?;
+        let cur = cur.trim_start();
+        let end = word_end(cur);
+        let nxt = cur[..end].to_string();
+        let remainder = cur[end..].trim_start();
+
+        if !remainder.is_empty() {
+            self.current = Some(remainder.to_string());
+        }
+        if nxt.is_empty() {
+            None
+        } else {
+            Some(nxt)
+        }
+    }
 }
 
 impl Iterator for Tokenizer {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut stop = usize::MAX;
-        let mut nxt= String::new();
-        let mut remainder = String::new();
-
-        if let Some(s) = &mut self.current {
-            let mut open = 0u8;
-            for (i, c) in s.chars().into_iter().enumerate() {
-                if c.eq(&'"') || c.eq(&'\'') {
-                    open = open ^ 1;
-                } else if c.eq(&' ') && open == 0 {
-                    stop = i + 1;
-                    break
-                } else {
-                    nxt.push(c);
-                }
+        self.next_raw().map(|w| unquote(&w))
+    }
+}
+
+/// checks if a raw word starts with one of the redirection operators
+pub fn is_redirection(word: &str) -> bool {
+    redirection_operator(word).is_some()
+}
+
+/// Redirection operators, longer ones are listed first
+const REDIRECTIONS: &[&str] = &[
+    "&>>", "&>", "<<<", "<<", "<>", "<&", "<", ">>", ">|", ">&", ">",
+];
+
+/// Splits a raw word like `2>&1`, `>>log` or `<` into an optional
+/// descriptor number, the operator and the attached target word
+pub fn redirection_operator(word: &str) -> Option<(Option<i32>, &'static str, &str)> {
+    let digits = word.find(|c: char| !c.is_ascii_digit()).unwrap_or(word.len());
+    let rest = &word[digits..];
+    let op = REDIRECTIONS.iter().find(|op| rest.starts_with(*op))?;
+
+    if digits > 0 && op.starts_with('&') {
+        return None;
+    }
+    let fd = if digits > 0 {
+        Some(word[..digits].parse().ok()?)
+    } else {
+        None
+    };
+    Some((fd, op, &rest[op.len()..]))
+}
+
+/// Split a line into words as they were typed
+pub fn raw_words(line: &str) -> Vec<String> {
+    let mut tok = Tokenizer {
+        current: Some(line.to_string()),
+        is_pipe: false,
+        has_redirection: false,
+    };
+    let mut words = vec![];
+    while let Some(w) = tok.next_raw() {
+        words.push(w);
+    }
+    words
+}
+
+/// Finds the byte index where the word at the start of `s` ends.
+/// Whitespace inside quotes, `$(...)`, `${...}`, backticks, an arithmetic
+/// command `(( ... ))`, a subshell `( ... )` and a group `{ ...; }`
+/// doesn't end a word. A `;` is always a word of its own.
+pub fn word_end(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+
+    if s.starts_with("((") {
+        i = closing_paren(s, 0).map_or(s.len(), |c| c + 1);
+    } else if s.starts_with('(') {
+        return closing_paren(s, 0).map_or(s.len(), |c| c + 1);
+    } else if is_brace_group(s) {
+        if let Some(end) = closing_brace(s) {
+            return end;
+        }
+    } else if s.starts_with(';') {
+        return 1;
+    }
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b' ' | b'\t' | b'\n' | b';' => return i,
+            b'\\' => i += 2,
+            b'\'' => i = find_from(s, i + 1, '\'').map_or(s.len(), |c| c + 1),
+            b'"' => i = closing_double_quote(s, i + 1).map_or(s.len(), |c| c + 1),
+            b'`' => i = closing_backtick(s, i + 1).map_or(s.len(), |c| c + 1),
+            b'$' if bytes.get(i + 1) == Some(&b'(') => {
+                i = closing_paren(s, i + 1).map_or(s.len(), |c| c + 1)
             }
-            if stop < s.len() {
-                remainder = s[stop..].to_string();
+            b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                i = find_from(s, i + 2, '}').map_or(s.len(), |c| c + 1)
             }
+            _ => i += 1,
         }
+    }
+    s.len()
+}
 
-        if remainder.is_empty() {
-            self.current = None
-        } else {
-            self.current = Some(remainder);
+/// checks if the text starts with an opening brace of a group command
+pub fn is_brace_group(s: &str) -> bool {
+    s.starts_with('{') && s[1..].starts_with(char::is_whitespace)
+}
+
+/// checks if a raw word is a subshell `( ... )` or a group `{ ...; }`
+pub fn is_group(word: &str) -> bool {
+    (word.starts_with('(') && !word.starts_with("((")) || is_brace_group(word)
+}
+
+/// Returns the index right after the `}` which closes the group opened
+/// at the start of `s`. A brace closes a group only in the place
+/// of a command name, so `{ echo }; }` is a group printing `}`.
+fn closing_brace(s: &str) -> Option<usize> {
+    let mut pos = 1;
+    let mut at_command = true;
+    loop {
+        let rest = &s[pos..];
+        let trimmed = rest.trim_start();
+        if trimmed.is_empty() {
+            return None;
         }
-        if nxt.is_empty() {
-            None
-        } else { 
-            Some(nxt)
+        pos += rest.len() - trimmed.len();
+
+        let end = word_end(trimmed);
+        let word = &trimmed[..end];
+        if at_command && word == "}" {
+            return Some(pos + 1);
         }
+        at_command = matches!(word, ";" | "&&" | "||" | "|");
+        pos += end;
+    }
+}
+
+fn find_from(s: &str, start: usize, c: char) -> Option<usize> {
+    s.get(start..)?.find(c).map(|i| i + start)
+}
+
+/// index of the double quote which closes a string started before `start`
+pub fn closing_double_quote(s: &str, start: usize) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => return Some(i),
+            b'\\' => i += 2,
+            b'$' if bytes.get(i + 1) == Some(&b'(') => i = closing_paren(s, i + 1)? + 1,
+            b'`' => i = closing_backtick(s, i + 1)? + 1,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// index of the backtick which closes a substitution started before `start`
+pub fn closing_backtick(s: &str, start: usize) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'`' => return Some(i),
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// index of the parenthesis matching the one at `open`,
+/// quotation marks inside the parenthesis are respected
+pub fn closing_paren(s: &str, open: usize) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut depth = 0;
+    let mut i = open;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            b'\\' => i += 1,
+            b'\'' => i = find_from(s, i + 1, '\'')?,
+            b'"' => i = closing_double_quote(s, i + 1)?,
+            b'`' => i = closing_backtick(s, i + 1)?,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Removes quotation marks and escaping backslashes from a word.
+/// Substitutions and arithmetic commands are left untouched.
+pub fn unquote(word: &str) -> String {
+    if word.starts_with("((") {
+        return word.to_string();
+    }
+
+    let mut res = String::new();
+    let mut chars = word.char_indices().peekable();
+    let mut quote: Option<char> = None;
+
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (Some('\''), _) => res.push(c),
+            (None, '\\') => {
+                if let Some((_, n)) = chars.next() {
+                    res.push(n);
+                }
+            }
+            (Some('"'), '\\') => match chars.peek() {
+                Some(&(_, n)) if matches!(n, '"' | '\\' | '$' | '`') => {
+                    res.push(n);
+                    chars.next();
+                }
+                _ => res.push(c),
+            },
+            (_, '$') if word[i + 1..].starts_with('(') => {
+                let end = closing_paren(word, i + 1).unwrap_or(word.len() - 1);
+                res.push_str(&word[i..=end]);
+                while chars.peek().is_some_and(|&(j, _)| j <= end) {
+                    chars.next();
+                }
+            }
+            _ => res.push(c),
+        }
+    }
+    res
+}
+
+/// Wrap a string in single quotation marks so it is read back as one word
+pub fn quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Quote the word only when it has characters special to the shell,
+/// for showing commands the way they could be typed
+pub fn quote_if_needed(word: &str) -> String {
+    let plain = |c: char| c.is_alphanumeric() || "-_./=:,+%@^".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        word.to_string()
+    } else {
+        quote(word)
     }
 }
 
@@ -198,23 +405,32 @@ impl Iterator for Tokenizer {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_quote_if_needed() {
+        assert_eq!("ls", quote_if_needed("ls"));
+        assert_eq!("--color=auto", quote_if_needed("--color=auto"));
+        assert_eq!("'a b'", quote_if_needed("a b"));
+        assert_eq!("''", quote_if_needed(""));
+        assert_eq!("'it'\\''s'", quote_if_needed("it's"));
+    }
+
     #[test]
     fn test_empty_string() {
-        let mut line = Tokenizer::new(&"");
+        let mut line = Tokenizer::new("");
         assert_eq!(None, line.next());
         assert_eq!(None, line.current);
     }
 
     #[test]
     fn test_two_word_string() {
-        let mut line = Tokenizer::new(&"Hello World");
+        let mut line = Tokenizer::new("Hello World");
         assert_eq!("Hello".to_string(), line.next().unwrap());
         assert_eq!("World".to_string(), line.current.unwrap());
     }
 
     #[test]
     fn test_multiple_calls() {
-        let mut line = Tokenizer::new(&"Hello Darkness My Old Friend");
+        let mut line = Tokenizer::new("Hello Darkness My Old Friend");
 
         assert_eq!("Hello".to_string(), line.next().unwrap());
         assert_eq!("Darkness".to_string(), line.next().unwrap());
@@ -226,7 +442,7 @@ mod tests {
 
     #[test]
     fn test_peek() {
-        let mut line = Tokenizer::new(&"Hello Darkness > \"My Oldie\"");
+        let mut line = Tokenizer::new("Hello Darkness > \"My Oldie\"");
         assert_eq!("Hello".to_string(), line.peek());
         assert_eq!("Hello".to_string(), line.next().unwrap());
         assert_eq!("Darkness".to_string(), line.peek());
@@ -238,28 +454,7 @@ mod tests {
     #[test]
     fn test_contains() {
         let line = Tokenizer::new("This line tests $ symbol");
-        assert!(line.contains(&"$"));
-    }
-
-    #[test]
-    fn test_args_before_redirection() {
-        let mut line = Tokenizer::new("Hello World > Bye");
-        let v = line.args_before_redirection();
-        assert_eq!(2, v.len());
-        assert_eq!("Hello".to_string(), *v[0]);
-        assert_eq!("World".to_string(), *v[1]);
-
-        let a = line.get_args();
-        assert_eq!(2, a.len());
-        assert_eq!("Bye".to_string(), *a[1]);
-    }
-
-    #[test]
-    fn test_commands_before_pipe() {
-        let mut line = Tokenizer::new("ls -a | cat");
-        let mut v = line.commands_before_pipe();
-        assert_eq!("ls".to_string(), v.next().unwrap());
-        assert_eq!("-a".to_string(), v.next().unwrap());
+        assert!(line.contains("$"));
     }
 
     #[test]
@@ -267,15 +462,99 @@ mod tests {
         let line = Tokenizer::new("this line starts with this");
         assert!(line.starts_with("this"));
     }
-    
+
     #[test]
     fn test_quotation_marks() {
         let mut line = Tokenizer::new("echo \"Hello World\" 'Rust Lang' Yay!");
-        
+
         assert_eq!("echo".to_string(), line.next().unwrap());
         assert_eq!("Hello World".to_string(), line.next().unwrap());
         assert_eq!("Rust Lang".to_string(), line.next().unwrap());
         assert_eq!("Yay!".to_string(), line.next().unwrap());
         assert_eq!(None, line.next());
     }
+
+    #[test]
+    fn test_mixed_quotes_and_escapes() {
+        let mut line = Tokenizer::new("echo \"it's\" 'say \"hi\"' a\\ b  c");
+        line.next();
+        assert_eq!("it's".to_string(), line.next().unwrap());
+        assert_eq!("say \"hi\"".to_string(), line.next().unwrap());
+        assert_eq!("a b".to_string(), line.next().unwrap());
+        assert_eq!("c".to_string(), line.next().unwrap());
+    }
+
+    #[test]
+    fn test_arithmetic_is_one_word() {
+        let mut line = Tokenizer::new("echo $(( 1 + 2 )) (( x && y )) done");
+        assert!(!line.is_pipe());
+        line.next();
+        assert_eq!("$(( 1 + 2 ))".to_string(), line.next().unwrap());
+        assert_eq!("(( x && y ))".to_string(), line.next().unwrap());
+        assert_eq!("done".to_string(), line.next().unwrap());
+    }
+
+    #[test]
+    fn test_redirection_operators() {
+        assert_eq!(Some((Some(2), ">&", "1")), redirection_operator("2>&1"));
+        assert_eq!(Some((None, ">>", "log")), redirection_operator(">>log"));
+        assert_eq!(Some((None, "&>", "")), redirection_operator("&>"));
+        assert_eq!(Some((None, "<<<", "text")), redirection_operator("<<<text"));
+        assert_eq!(None, redirection_operator("2&>x"));
+        assert_eq!(None, redirection_operator("a>b"));
+        assert_eq!(None, redirection_operator("42"));
+    }
+
+    #[test]
+    fn test_command_list() {
+        let mut line = Tokenizer::new("a 1; b && c || d;e ';'");
+        let list: Vec<_> = line
+            .command_list()
+            .into_iter()
+            .map(|(t, op)| (t.remainder().to_string(), op))
+            .collect();
+        assert_eq!(
+            vec![
+                ("a 1".to_string(), ";".to_string()),
+                ("b".to_string(), "&&".to_string()),
+                ("c".to_string(), "||".to_string()),
+                ("d".to_string(), ";".to_string()),
+                ("e ';'".to_string(), ";".to_string()),
+            ],
+            list
+        );
+    }
+
+    #[test]
+    fn test_short_and_blank_commands() {
+        let commands = |line: &str| -> Vec<String> {
+            Tokenizer::new(line)
+                .command_list()
+                .into_iter()
+                .map(|(t, _)| t.remainder().to_string())
+                .collect()
+        };
+        assert_eq!(vec!["w", ":", "[ -n x ]", "l"], commands("w; : && [ -n x ] || l"));
+        assert!(commands("").is_empty());
+        assert!(commands(" \t ").is_empty());
+        assert!(commands(" ; ").is_empty());
+    }
+
+    #[test]
+    fn test_groups_are_one_word() {
+        let words = raw_words("( cd /tmp && ls ) | { echo a; echo }; } > out");
+        assert_eq!(
+            vec!["( cd /tmp && ls )", "|", "{ echo a; echo }; }", ">", "out"],
+            words
+        );
+        let words = raw_words("(echo a)>out { nested { x; }; }");
+        assert_eq!(vec!["(echo a)", ">out", "{ nested { x; }; }"], words);
+    }
+
+    #[test]
+    fn test_quoted_operators() {
+        let line = Tokenizer::new("echo '|' \">\"");
+        assert!(!line.is_pipe());
+        assert!(!line.has_redirection());
+    }
 }
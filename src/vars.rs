@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// Shell variables which are not exported to the environment
+    static ref VARS: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+
+    /// Exit status of the last executed command, exposed as `$?`
+    static ref STATUS: Mutex<i32> = Mutex::new(0);
+}
+
+/// Look up a variable. Shell variables shadow the environment.
+pub fn get(name: &str) -> Option<String> {
+    if name == "?" {
+        return Some(status().to_string());
+    }
+    if let Some(v) = VARS.lock().unwrap().get(name) {
+        return Some(v.clone());
+    }
+    env::var(name).ok()
+}
+
+/// Set a variable. If the variable is already exported the
+/// environment is updated, otherwise it stays local to the shell.
+pub fn set(name: &str, value: &str) {
+    if env::var_os(name).is_some() {
+        env::set_var(name, value);
+    } else {
+        VARS.lock().unwrap().insert(name.to_string(), value.to_string());
+    }
+}
+
+/// Set a variable and export it to the environment of child processes
+pub fn export(name: &str, value: &str) {
+    VARS.lock().unwrap().remove(name);
+    env::set_var(name, value);
+}
+
+/// Run `f` with the variables set and exported for it only, like for
+/// `NAME=value command`. They get their old values back afterwards.
+pub fn with_temporary<T>(assignments: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+    let saved: Vec<_> = assignments
+        .iter()
+        .map(|(name, value)| {
+            let old = (VARS.lock().unwrap().get(*name).cloned(), env::var(name).ok());
+            export(name, value);
+            (*name, old)
+        })
+        .collect();
+    let res = f();
+    // in reverse, so a name given twice ends with its first old value
+    for (name, (local, exported)) in saved.into_iter().rev() {
+        unset(name);
+        if let Some(v) = exported {
+            env::set_var(name, v);
+        }
+        if let Some(v) = local {
+            VARS.lock().unwrap().insert(name.to_string(), v);
+        }
+    }
+    res
+}
+
+/// Elements of an array are the shell variables `name[0]`, `name[1]`, ...
+fn element(name: &str, index: usize) -> String {
+    format!("{}[{}]", name, index)
+}
+
+/// Replace the variable, or all elements of an array, by the values
+pub fn set_array(name: &str, values: &[String]) {
+    unset(name);
+    let prefix = format!("{}[", name);
+    let mut vars = VARS.lock().unwrap();
+    vars.retain(|k, _| !k.starts_with(&prefix));
+    for (i, value) in values.iter().enumerate() {
+        vars.insert(element(name, i), value.clone());
+    }
+}
+
+/// Elements of an array from index 0 up to the first unset one
+pub fn array(name: &str) -> Vec<String> {
+    (0..).map_while(|i| get(&element(name, i))).collect()
+}
+
+/// Remove a variable from both the shell and the environment
+pub fn unset(name: &str) {
+    VARS.lock().unwrap().remove(name);
+    env::remove_var(name);
+}
+
+/// checks if string is a valid variable name
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// If the word has a form of `NAME=value` returns its parts
+pub fn split_assignment(word: &str) -> Option<(&str, &str)> {
+    let eq = word.find('=')?;
+    if is_valid_name(&word[..eq]) {
+        Some((&word[..eq], &word[eq + 1..]))
+    } else {
+        None
+    }
+}
+
+pub fn status() -> i32 {
+    *STATUS.lock().unwrap()
+}
+
+pub fn set_status(code: i32) {
+    *STATUS.lock().unwrap() = code;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get() {
+        set("CR4SH_TEST_VAR", "42");
+        assert_eq!(Some("42".to_string()), get("CR4SH_TEST_VAR"));
+        unset("CR4SH_TEST_VAR");
+        assert_eq!(None, get("CR4SH_TEST_VAR"));
+    }
+
+    #[test]
+    fn test_temporary() {
+        set("CR4SH_TEMP_VAR", "old");
+        let inside = with_temporary(&[("CR4SH_TEMP_VAR", "1"), ("CR4SH_TEMP_NEW", "2")], || {
+            (get("CR4SH_TEMP_VAR"), env::var("CR4SH_TEMP_NEW").ok())
+        });
+        assert_eq!((Some("1".to_string()), Some("2".to_string())), inside);
+        assert_eq!(Some("old".to_string()), get("CR4SH_TEMP_VAR"));
+        assert_eq!(None, env::var("CR4SH_TEMP_VAR").ok());
+        assert_eq!(None, get("CR4SH_TEMP_NEW"));
+        unset("CR4SH_TEMP_VAR");
+    }
+
+    #[test]
+    fn test_arrays() {
+        set("cr4sh_arr[5]", "stale");
+        set_array("cr4sh_arr", &["a".to_string(), "b".to_string()]);
+        assert_eq!(vec!["a", "b"], array("cr4sh_arr"));
+        assert_eq!(None, get("cr4sh_arr[5]"));
+        set_array("cr4sh_arr", &[]);
+        assert!(array("cr4sh_arr").is_empty());
+    }
+
+    #[test]
+    fn test_assignment() {
+        assert_eq!(Some(("x", "5")), split_assignment("x=5"));
+        assert_eq!(Some(("_a1", "")), split_assignment("_a1="));
+        assert_eq!(None, split_assignment("1x=5"));
+        assert_eq!(None, split_assignment("echo"));
+    }
+}